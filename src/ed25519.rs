@@ -0,0 +1,192 @@
+//! Signatures checked by the Ed25519 sig-verify program earlier in the transaction
+
+use solana_program::{
+    ed25519_program, instruction::Instruction, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use crate::error::EscrowError;
+
+/// Size of the `Ed25519SignatureOffsets` entry following the two byte header
+const OFFSETS_LEN: usize = 14;
+/// Instruction index meaning the data lives in the Ed25519 instruction itself
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Message a release authority signs off-chain to authorize paying up to `amount`
+/// in total from `escrow` until `expiry`
+pub fn release_message(escrow: &Pubkey, amount: u64, expiry: u64) -> Vec<u8> {
+    [&escrow.to_bytes()[..], &amount.to_le_bytes(), &expiry.to_le_bytes()].concat()
+}
+
+/// Public key and message of the single signature verified by `instruction`,
+/// only signatures with their data inline in the Ed25519 instruction are accepted
+pub fn verified_signature(instruction: &Instruction) -> Result<(Pubkey, &[u8]), ProgramError> {
+    if instruction.program_id != ed25519_program::id() {
+        return Err(EscrowError::InvalidReleaseAuthorization.into());
+    }
+    let data = &instruction.data;
+    if data.len() < 2 + OFFSETS_LEN || data[0] != 1 {
+        return Err(EscrowError::InvalidReleaseAuthorization.into());
+    }
+
+    let offsets = &data[2..2 + OFFSETS_LEN];
+    let read_u16 = |i: usize| u16::from_le_bytes([offsets[i * 2], offsets[i * 2 + 1]]);
+    let signature_instruction_index = read_u16(1);
+    let public_key_offset = read_u16(2) as usize;
+    let public_key_instruction_index = read_u16(3);
+    let message_data_offset = read_u16(4) as usize;
+    let message_data_size = read_u16(5) as usize;
+    let message_instruction_index = read_u16(6);
+
+    if signature_instruction_index != CURRENT_INSTRUCTION
+        || public_key_instruction_index != CURRENT_INSTRUCTION
+        || message_instruction_index != CURRENT_INSTRUCTION
+    {
+        return Err(EscrowError::InvalidReleaseAuthorization.into());
+    }
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(EscrowError::InvalidReleaseAuthorization)?;
+    let message = data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(EscrowError::InvalidReleaseAuthorization)?;
+
+    let public_key = Pubkey::try_from(public_key).map_err(|_| EscrowError::InvalidReleaseAuthorization)?;
+    Ok((public_key, message))
+}
+
+/// Fails unless `instruction` verified `authority`'s signature of the release message
+/// for exactly this `escrow`, `amount` and `expiry`
+pub fn assert_release_authorized(
+    instruction: &Instruction,
+    authority: &Pubkey,
+    escrow: &Pubkey,
+    amount: u64,
+    expiry: u64,
+) -> Result<(), ProgramError> {
+    let (signer, message) = verified_signature(instruction)?;
+    if signer != *authority || message != release_message(escrow, amount, expiry).as_slice() {
+        return Err(EscrowError::InvalidReleaseAuthorization.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE_OFFSET: u16 = 2 + OFFSETS_LEN as u16;
+    const PUBLIC_KEY_OFFSET: u16 = SIGNATURE_OFFSET + 64;
+    const MESSAGE_OFFSET: u16 = PUBLIC_KEY_OFFSET + 32;
+
+    /// Ed25519 instruction data as laid out by the sig-verify program, everything inline
+    fn ed25519_data(count: u8, offsets: [u16; 7], public_key: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let mut data = vec![count, 0];
+        for offset in offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(&[7; 64]);
+        data.extend_from_slice(&public_key.to_bytes());
+        data.extend_from_slice(message);
+        data
+    }
+
+    fn inline_offsets(message_len: usize) -> [u16; 7] {
+        [
+            SIGNATURE_OFFSET,
+            CURRENT_INSTRUCTION,
+            PUBLIC_KEY_OFFSET,
+            CURRENT_INSTRUCTION,
+            MESSAGE_OFFSET,
+            message_len as u16,
+            CURRENT_INSTRUCTION,
+        ]
+    }
+
+    fn ed25519_instruction(data: Vec<u8>) -> Instruction {
+        Instruction { program_id: ed25519_program::id(), accounts: vec![], data }
+    }
+
+    #[test]
+    fn test_inline_signature() {
+        let authority = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let message = release_message(&escrow, 500, 1_000);
+        let instruction = ed25519_instruction(ed25519_data(1, inline_offsets(message.len()), &authority, &message));
+
+        assert_eq!(verified_signature(&instruction), Ok((authority, message.as_slice())));
+        assert_eq!(assert_release_authorized(&instruction, &authority, &escrow, 500, 1_000), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_other_program() {
+        let message = release_message(&Pubkey::new_unique(), 500, 1_000);
+        let mut instruction =
+            ed25519_instruction(ed25519_data(1, inline_offsets(message.len()), &Pubkey::new_unique(), &message));
+        instruction.program_id = Pubkey::new_unique();
+        assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+    }
+
+    #[test]
+    fn test_rejects_other_instruction_index() {
+        let message = release_message(&Pubkey::new_unique(), 500, 1_000);
+        // signature, public key and message may each point into another instruction
+        for field in [1, 3, 6] {
+            let mut offsets = inline_offsets(message.len());
+            offsets[field] = 0;
+            let instruction = ed25519_instruction(ed25519_data(1, offsets, &Pubkey::new_unique(), &message));
+            assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+        }
+    }
+
+    #[test]
+    fn test_rejects_out_of_bounds_offsets() {
+        let message = release_message(&Pubkey::new_unique(), 500, 1_000);
+        let data = ed25519_data(1, inline_offsets(message.len()), &Pubkey::new_unique(), &message);
+        let len = data.len() as u16;
+
+        let mut offsets = inline_offsets(message.len());
+        offsets[2] = len - 31;
+        let instruction = ed25519_instruction(ed25519_data(1, offsets, &Pubkey::new_unique(), &message));
+        assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+
+        let mut offsets = inline_offsets(message.len() + 1);
+        offsets[4] = MESSAGE_OFFSET;
+        let instruction = ed25519_instruction(ed25519_data(1, offsets, &Pubkey::new_unique(), &message));
+        assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+
+        let mut offsets = inline_offsets(message.len());
+        offsets[4] = u16::MAX;
+        offsets[5] = u16::MAX;
+        let instruction = ed25519_instruction(ed25519_data(1, offsets, &Pubkey::new_unique(), &message));
+        assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+
+        // not even room for the offsets
+        let instruction = ed25519_instruction(vec![1, 0, 0, 0]);
+        assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+    }
+
+    #[test]
+    fn test_rejects_signature_count_other_than_one() {
+        let message = release_message(&Pubkey::new_unique(), 500, 1_000);
+        for count in [0, 2] {
+            let data = ed25519_data(count, inline_offsets(message.len()), &Pubkey::new_unique(), &message);
+            let instruction = ed25519_instruction(data);
+            assert_eq!(verified_signature(&instruction), Err(EscrowError::InvalidReleaseAuthorization.into()));
+        }
+    }
+
+    #[test]
+    fn test_rejects_message_for_other_release() {
+        let authority = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let message = release_message(&escrow, 500, 1_000);
+        let instruction = ed25519_instruction(ed25519_data(1, inline_offsets(message.len()), &authority, &message));
+
+        let rejected = Err(EscrowError::InvalidReleaseAuthorization.into());
+        assert_eq!(assert_release_authorized(&instruction, &authority, &Pubkey::new_unique(), 500, 1_000), rejected);
+        assert_eq!(assert_release_authorized(&instruction, &authority, &escrow, 501, 1_000), rejected);
+        assert_eq!(assert_release_authorized(&instruction, &authority, &escrow, 500, 1_001), rejected);
+        assert_eq!(assert_release_authorized(&instruction, &Pubkey::new_unique(), &escrow, 500, 1_000), rejected);
+    }
+}
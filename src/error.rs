@@ -12,10 +12,96 @@ pub enum TokenError {
     EscrowMismatch
 }
 
+/// Errors of the escrow program, numbered from 100 so their custom codes never
+/// overlap with `TokenError`
 #[derive(Clone, Debug, Eq, Error, PartialEq)]
 pub enum EscrowError {
-    #[error("24 hours not passed yet!")]
-    WithdrawTimeLimitNotExceed,
+    #[error("Lock duration not passed yet!")]
+    WithdrawTimeLimitNotExceed = 100,
+    #[error("Escrow already unlocked, it can no longer be cancelled!")]
+    CancelTimeLimitExceeded,
+    #[error("Withdraw amount exceeds the remaining escrow balance")]
+    WithdrawAmountExceeded,
+    #[error("Escrow account not owned by this program")]
+    InvalidEscrowOwner,
+    #[error("Escrow account does not match its program address")]
+    InvalidEscrowAddress,
+    #[error("Vault account does not match its program address")]
+    InvalidVaultAddress,
+    #[error("Lock duration outside of the configured range")]
+    LockDurationOutOfRange,
+    #[error("Config account does not match its program address")]
+    InvalidConfigAddress,
+    #[error("Config account already initialized")]
+    ConfigAlreadyInitialized,
+    #[error("Signer is not the config admin")]
+    AdminMismatch,
+    #[error("Arithmetic overflow")]
+    Overflow,
+    #[error("Invalid release schedule")]
+    InvalidSchedule,
+    #[error("Nothing released to withdraw yet")]
+    NothingToWithdraw,
+    #[error("Escrow is not a stream")]
+    NotAStream,
+    #[error("Stream already stopped")]
+    StreamStopped,
+    #[error("Recipient shares must be unique and sum to 10000 basis points")]
+    InvalidShares,
+    #[error("Receiver is not a recipient of this escrow")]
+    RecipientNotFound,
+    #[error("Share already claimed")]
+    AlreadyClaimed,
+    #[error("Invalid merkle proof")]
+    InvalidProof,
+    #[error("Preimage does not match the hashlock")]
+    InvalidPreimage,
+    #[error("Hashlock deadline passed")]
+    HashlockExpired,
+    #[error("Refund timeout not reached yet")]
+    RefundTimeoutNotReached,
+    #[error("Escrow has no arbiter")]
+    NoArbiter,
+    #[error("Signer is not the escrow arbiter")]
+    ArbiterMismatch,
+    #[error("Escrow is under dispute")]
+    EscrowDisputed,
+    #[error("Invalid approvers or threshold")]
+    InvalidApprovers,
+    #[error("Signer is not an approver")]
+    NotAnApprover,
+    #[error("Approver already approved")]
+    AlreadyApproved,
+    #[error("Not enough approvals yet")]
+    ApprovalsPending,
+    #[error("Missing or invalid release authorization")]
+    InvalidReleaseAuthorization,
+    #[error("Release authorization expired")]
+    AuthorizationExpired,
+    #[error("Invalid oracle account")]
+    InvalidOracleAccount,
+    #[error("Oracle condition not met")]
+    OracleConditionNotMet,
+    #[error("Vault balance is lower than the escrowed amount")]
+    VaultUnderfunded,
+    #[error("Decimals do not match the mint")]
+    DecimalsMismatch,
+    #[error("Invalid UI amount")]
+    InvalidUiAmount,
+    #[error("Mint is not an NFT")]
+    NotAnNft,
+    #[error("Invalid metadata account")]
+    InvalidMetadataAccount,
+    #[error("Metadata does not match the expected collection or creator")]
+    MetadataMismatch,
+    #[error("Invalid basket legs")]
+    InvalidBasket,
+    #[error("Distribution expired")]
+    DistributionExpired,
+    #[error("Distribution not expired yet")]
+    DistributionNotExpired,
+    #[error("Mint charges a transfer fee")]
+    TransferFeeNotSupported,
 }
 
 impl From<TokenError> for ProgramError {
@@ -29,3 +115,14 @@ impl From<EscrowError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escrow_codes_do_not_overlap_token_codes() {
+        assert_eq!(ProgramError::from(TokenError::EscrowMismatch), ProgramError::Custom(2));
+        assert_eq!(ProgramError::from(EscrowError::WithdrawTimeLimitNotExceed), ProgramError::Custom(100));
+    }
+}
@@ -1,32 +1,468 @@
 use solana_program::program_error::ProgramError;
-use solana_program::{
-    msg, 
-    program_error,
-};
+use solana_program::msg;
 
-use crate::state::{TransferInput, WithdrawInput, InitTokenInput, WithdrawTokenInput};
+use crate::state::{TransferInput, WithdrawInput, InitTokenInput, WithdrawTokenInput, ConfigInput, Schedule, TopUpInput, TopUpTokenInput, UpdateScheduleInput, SplitInput, BatchTransferInput, DistributorInput, ClaimInput, ResolveDisputeInput, ReleaseInput, SwapInput, NftTransferInput, BasketInput, WithdrawBasketInput};
+use borsh::BorshDeserialize;
 pub enum TransferInstruction{ 
     /// Create a transfer with a escrow account created and funded by sender
     /// account should have a total_lamport= program_rent_account+amount_to_send.
+    /// The escrow is a PDA of `[PREFIX, sender, receiver, nonce]` so a sender can keep
+    /// several escrows open by picking different nonces, see `find_escrow_address`.
+    /// `lock_duration` must be within the bounds stored in the config account.
+    /// An optional borsh encoded `Schedule` may follow the fixed fields, `Schedule::Cliff` otherwise.
+    /// The escrow account is sized from the encoded schedule, e.g. the number of milestones.
+    /// A borsh encoded `Option<[u8; 32]>` SHA-256 hashlock may follow the schedule, turning the
+    /// escrow into a hash time-locked contract: the receiver must `Withdraw` with the preimage
+    /// before `start_time + lock_duration`, after which the sender can `Cancel` for a refund.
+    /// A borsh encoded `Option<Pubkey>` arbiter may follow the hashlock, see `ResolveDispute`,
+    /// then a borsh encoded `Option<Approvals>` requiring M-of-N approvals before withdrawal,
+    /// then a borsh encoded `Option<Pubkey>` release authority, see `AuthorizedRelease`,
+    /// then a borsh encoded `Option<OracleCondition>` gating withdrawals on a data feed.
+    /// On top of `amount_to_send` the sender funds the vault with the rent-exempt minimum of a
+    /// data-less account, it is returned to the sender when the escrow closes.
+    /// Split and basket vaults holding lamports are funded the same way.
     ///
     /// Accounts expected:
     ///
     /// `[writable]` escrow account, it will hold all necessary info about the trade.
     /// `[signer]` sender account
     /// `[]` receiver account
+    /// `[]` system program
+    /// `[writable]` vault account, PDA of `[PREFIX_VAULT, escrow]`
+    /// `[]` config account
     CreateTranfer(TransferInput),
 
-    /// Withdraw for receiver
+    /// Withdraw for receiver, `amount` may be any part of the remaining escrow balance.
+    /// Hashlocked escrows expect the preimage as the remaining instruction data.
+    /// For vesting, periodic, milestone and stream schedules `amount` is ignored and everything
+    /// released so far, e.g. all matured tranches or the accrued stream, is paid.
+    /// The escrow is closed and its rent returned to the sender once fully withdrawn.
     ///
     /// Accounts expected:
     ///
     /// `[writable]` escrow account, it will hold all necessary info about the trade.
+    /// `[writable]` sender account
     /// `[signer]` receiver account
+    /// `[]` system program
+    /// `[writable]` vault account
+    /// `[]` oracle account, only for escrows with an oracle condition
     Withdraw(WithdrawInput),
 
+    /// Create a token transfer, the escrow is a PDA of `[PREFIX_TOKEN, sender, receiver, nonce]`
+    /// and the tokens are held by the associated token account of the vault PDA.
+    /// Token instructions accept mints of either the SPL Token or the Token-2022 program,
+    /// the token program account passed must be the owner of the mint.
+    /// The config account is expected after the vault account.
+    /// Like for `CreateTranfer`, an optional `Schedule`, `Option<Approvals>`,
+    /// `Option<OracleCondition>`, `Option<UiAmount>` and `Option<Pubkey>` arbiter may follow,
+    /// see `ResolveDisputeToken`.
+    /// The mint's decimals are stored and every token move is a `transfer_checked`.
+    /// The escrow holds what the vault actually received after Token-2022 transfer fees,
+    /// periodic and milestone schedules need the full amount and are rejected with
+    /// `InvalidSchedule` for mints charging a fee.
     TransferToken(InitTokenInput),
 
+    /// Withdraw tokens for receiver, partial withdrawals are allowed like `Withdraw`.
+    /// The vault token account and the escrow are closed once fully withdrawn, a Token-2022
+    /// mint must then be writable so transfer fees withheld by the vault can be harvested.
+    /// Escrows with an oracle condition expect the oracle account after the other accounts.
+    /// A borsh encoded `Option<UiAmount>` may follow the amount and replaces it when present.
     WithdrawToken(WithdrawTokenInput),
+
+    /// Cancel a native transfer before it unlocks, refunding the vault balance to the sender
+    /// and closing the escrow account. Hashlocked escrows can only be refunded after the deadline.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account, it will be closed and its rent returned to the sender.
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[writable]` vault account
+    Cancel,
+
+    /// Cancel a token transfer before it unlocks, returning the vault tokens to the sender's
+    /// associated token account and closing both the vault token account and the escrow account.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account, it will be closed and its rent returned to the sender.
+    /// `[signer, writable]` sender account
+    /// `[]` vault account
+    /// `[writable]` token mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program
+    /// `[writable]` vault associated token account
+    /// `[writable]` sender associated token account
+    CancelToken,
+
+    /// Create the program config holding the allowed lock duration range. Only the program's
+    /// upgrade authority can create it and becomes the config admin.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` config account, PDA of `[PREFIX_CONFIG]`
+    /// `[signer, writable]` admin account, the program's upgrade authority
+    /// `[]` system program
+    /// `[]` program data account of the program, owned by the upgradeable BPF loader
+    InitConfig(ConfigInput),
+
+    /// Change the allowed lock duration range, existing escrows keep their lock duration.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` config account
+    /// `[signer]` admin account
+    UpdateConfig(ConfigInput),
+
+    /// Add lamports to an existing native escrow, the schedule grows with the deposit:
+    /// periodic top ups must split evenly over all periods, milestone top ups are paid with
+    /// the last milestone and streams must still be running.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[writable]` vault account
+    TopUp(TopUpInput),
+
+    /// Add tokens to an existing token escrow, the schedule grows like for `TopUp`.
+    /// A borsh encoded `Option<UiAmount>` may follow the amount and replaces it when present.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` sender account
+    /// `[]` vault account
+    /// `[]` token mint
+    /// `[]` token program
+    /// `[writable]` vault associated token account
+    /// `[writable]` sender associated token account
+    TopUpToken(TopUpTokenInput),
+
+    /// Stop a native stream, the unstreamed lamports go back to the sender while the
+    /// accrued part stays withdrawable by the receiver.
+    ///
+    /// Accounts expected: same as `Cancel`
+    StopStream,
+
+    /// Stop a token stream, the unstreamed tokens go back to the sender while the
+    /// accrued part stays withdrawable by the receiver.
+    ///
+    /// Accounts expected: same as `CancelToken`
+    StopStreamToken,
+
+    /// Extend the end time of a linear native escrow or change the rate of a native stream.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` sender account
+    UpdateSchedule(UpdateScheduleInput),
+
+    /// Extend the end time of a linear token escrow or change the rate of a token stream.
+    ///
+    /// Accounts expected: same as `UpdateSchedule`
+    UpdateScheduleToken(UpdateScheduleInput),
+
+    /// Create a native escrow split between several receivers by basis point shares,
+    /// the borsh encoded `SplitInput` follows the tag. The escrow is a PDA of
+    /// `[PREFIX_SPLIT, sender, nonce]`, see `find_split_escrow_address`.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` split escrow account
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[writable]` vault account, PDA of `[PREFIX_VAULT, escrow]`
+    /// `[]` config account
+    CreateSplit(SplitInput),
+
+    /// Withdraw the signing receiver's share of a split escrow once unlocked.
+    /// The escrow is closed and the remaining rent and dust go to the sender after the last claim.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` split escrow account
+    /// `[writable]` sender account
+    /// `[signer, writable]` receiver account
+    /// `[]` system program
+    /// `[writable]` vault account
+    WithdrawSplit,
+
+    /// Create and fund one native cliff escrow per entry in a single instruction,
+    /// the borsh encoded `BatchTransferInput` follows the tag. Each escrow is derived
+    /// like for `CreateTranfer` from the sender, the entry's receiver and nonce.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[]` config account
+    /// then for every entry, in order:
+    /// `[writable]` escrow account
+    /// `[writable]` vault account
+    BatchCreateTransfer(BatchTransferInput),
+
+    /// Fund a token vault paid out to receivers proving their `(receiver, amount)` leaf
+    /// against `merkle_root`, see `merkle::distribution_leaf`. The distributor is a PDA of
+    /// `[PREFIX_DISTRIBUTOR, sender, nonce]` and the borsh encoded `DistributorInput` follows the tag.
+    /// Claims are open until `expires_at`, then the sender can `ClawbackDistribution` the rest.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` distributor account
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[]` token mint
+    /// `[]` token program
+    /// `[writable]` sender associated token account
+    /// `[writable]` vault associated token account
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    /// `[]` vault account, PDA of `[PREFIX_VAULT, distributor]`
+    CreateDistributor(DistributorInput),
+
+    /// Claim from a distributor with a merkle proof before it expires, the borsh encoded
+    /// `ClaimInput` follows the tag. Each receiver can claim once, recorded by the claim status PDA.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` distributor account
+    /// `[signer, writable]` receiver account
+    /// `[writable]` claim status account, PDA of `[PREFIX_CLAIM, distributor, receiver]`
+    /// `[]` vault account
+    /// `[]` system program
+    /// `[]` token mint
+    /// `[]` token program
+    /// `[writable]` vault associated token account
+    /// `[writable]` receiver associated token account
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    ClaimDistribution(ClaimInput),
+
+    /// Freeze a native escrow that has an arbiter, `Withdraw`, `Cancel`, `StopStream`, `TopUp`
+    /// and `UpdateSchedule` are rejected until the arbiter calls `ResolveDispute`.
+    /// See `RaiseDisputeToken` for token escrows.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` sender or receiver account
+    RaiseDispute,
+
+    /// Arbiter ruling on a native escrow, allowed at any time whether disputed or not,
+    /// see `ResolveDisputeToken` for token escrows.
+    /// The borsh encoded `ResolveDisputeInput` follows the tag. Releasing pays the remaining
+    /// balance to the receiver, refunding returns the vault balance to the sender, and
+    /// the escrow account is closed to the sender either way.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` arbiter account
+    /// `[writable]` sender account
+    /// `[writable]` receiver account
+    /// `[]` system program
+    /// `[writable]` vault account
+    ResolveDispute(ResolveDisputeInput),
+
+    /// Record the approval of one approver of a native escrow, see `Approvals`.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` approver account
+    Approve,
+
+    /// Record the approval of one approver of a token escrow, see `Approvals`.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` approver account
+    ApproveToken,
+
+    /// Pay a native escrow to its receiver on behalf of its release authority, without the
+    /// authority signing the transaction. The instruction right before this one must be an
+    /// Ed25519 program instruction verifying the authority's signature of
+    /// `ed25519::release_message(escrow, amount, expiry)`. `amount` is the total authorized so
+    /// far so replaying the same authorization pays nothing more. Lock time and schedule are
+    /// bypassed, a disputed escrow is still frozen.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[writable]` sender account
+    /// `[writable]` receiver account
+    /// `[]` system program
+    /// `[writable]` vault account
+    /// `[]` instructions sysvar
+    AuthorizedRelease(ReleaseInput),
+
+    /// Deposit `amount_a` of token A in a vault, to be swapped for `amount_b` of token B.
+    /// Each mint comes with the token program owning it, so SPL Token and Token-2022
+    /// mints can be swapped for each other. Mint B must not charge a transfer fee, the
+    /// initializer receives exactly `amount_b`.
+    /// The swap is a PDA of `[PREFIX_SWAP, initializer, nonce]` and the borsh encoded
+    /// `SwapInput` follows the tag.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` swap account
+    /// `[signer, writable]` initializer account
+    /// `[]` system program
+    /// `[]` token A mint
+    /// `[]` token B mint
+    /// `[]` token program owning mint A
+    /// `[]` token program owning mint B
+    /// `[writable]` initializer token A associated token account
+    /// `[writable]` vault token A associated token account
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    /// `[]` vault account, PDA of `[PREFIX_VAULT, swap]`
+    CreateSwap(SwapInput),
+
+    /// Atomically send `amount_b` of token B to the initializer and receive the token A
+    /// deposit. The vault token account and the swap account are closed to the initializer.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` swap account
+    /// `[signer, writable]` taker account
+    /// `[writable]` initializer account
+    /// `[]` vault account
+    /// `[]` system program
+    /// `[writable]` token A mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token B mint
+    /// `[]` token program owning mint A
+    /// `[]` token program owning mint B
+    /// `[writable]` vault token A associated token account
+    /// `[writable]` taker token A associated token account, created if missing
+    /// `[writable]` taker token B account
+    /// `[writable]` initializer token B associated token account, created if missing
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    TakeSwap,
+
+    /// Return the token A deposit to the initializer and close the swap.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` swap account
+    /// `[signer, writable]` initializer account
+    /// `[]` vault account
+    /// `[writable]` token A mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program owning mint A
+    /// `[writable]` vault token A associated token account
+    /// `[writable]` initializer token A account
+    CancelSwap,
+
+    /// Time-lock an NFT to a receiver: a `TransferToken` escrow of exactly one token of a
+    /// mint with supply 1, zero decimals and no mint authority, withdrawn or cancelled with
+    /// `WithdrawToken` and `CancelToken`. The borsh encoded `NftTransferInput` follows the tag.
+    ///
+    /// Accounts expected: the accounts of `TransferToken`, followed by
+    /// `[]` metadata account of the mint, only when a collection or creator is expected
+    TransferNft(NftTransferInput),
+
+    /// Lock lamports and several token legs for one receiver. The basket is a PDA of
+    /// `[PREFIX_BASKET, sender, receiver, nonce]` and the borsh encoded `BasketInput` follows the tag.
+    /// A basket holds at most `MAX_BASKET_LEGS` token legs.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` basket account
+    /// `[signer, writable]` sender account
+    /// `[]` receiver account
+    /// `[]` system program
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    /// `[writable]` vault account, PDA of `[PREFIX_VAULT, basket]`
+    /// `[]` config account
+    /// then for every leg, in order:
+    /// `[]` token mint
+    /// `[]` token program owning the mint
+    /// `[writable]` sender token account
+    /// `[writable]` vault associated token account
+    CreateBasket(BasketInput),
+
+    /// Release one leg or every unclaimed leg once the basket unlocked, the borsh encoded
+    /// `WithdrawBasketInput` follows the tag. A leg is either the lamports or a token leg.
+    /// Each released token leg's vault token account is closed to the sender. Once every leg
+    /// was released the vault's rent-exempt reserve and the basket account go to the sender.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` basket account
+    /// `[writable]` sender account
+    /// `[signer, writable]` receiver account
+    /// `[]` system program
+    /// `[]` rent sysvar
+    /// `[]` associated token program
+    /// `[writable]` vault account
+    /// then for every released leg, in order:
+    /// `[writable]` token mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program owning the mint
+    /// `[writable]` vault associated token account
+    /// `[writable]` receiver associated token account, created if missing
+    WithdrawBasket(WithdrawBasketInput),
+
+    /// Refund every unclaimed leg to the sender before the basket unlocks and close the basket,
+    /// its vault token accounts and the vault.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` basket account
+    /// `[signer, writable]` sender account
+    /// `[]` system program
+    /// `[writable]` vault account
+    /// then for every unclaimed token leg, in order:
+    /// `[writable]` token mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program owning the mint
+    /// `[writable]` vault associated token account
+    /// `[writable]` sender token account
+    CancelBasket,
+
+    /// Freeze a token escrow that has an arbiter like `RaiseDispute`, `WithdrawToken`, `CancelToken`,
+    /// `StopStreamToken`, `TopUpToken` and `UpdateScheduleToken` are rejected until the arbiter
+    /// calls `ResolveDisputeToken`.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` sender or receiver account
+    RaiseDisputeToken,
+
+    /// Arbiter ruling on a token escrow, allowed at any time whether disputed or not.
+    /// The borsh encoded `ResolveDisputeInput` follows the tag. The vault tokens go to the
+    /// receiver's or the sender's token account, then the vault token account and the escrow
+    /// account are closed to the sender either way.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` escrow account
+    /// `[signer]` arbiter account
+    /// `[writable]` sender account
+    /// `[]` vault account
+    /// `[writable]` token mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program
+    /// `[writable]` vault associated token account
+    /// `[writable]` receiver token account when releasing, sender token account when refunding
+    ResolveDisputeToken(ResolveDisputeInput),
+
+    /// Return the unclaimed tokens of an expired distributor to the sender, then close the
+    /// vault token account and the distributor account to the sender.
+    ///
+    /// Accounts expected:
+    ///
+    /// `[writable]` distributor account
+    /// `[signer, writable]` sender account
+    /// `[]` vault account
+    /// `[writable]` token mint, writable for Token-2022 mints so withheld transfer fees can be harvested
+    /// `[]` token program
+    /// `[writable]` vault associated token account
+    /// `[writable]` sender associated token account
+    ClawbackDistribution,
 }
 
 impl TransferInstruction{
@@ -37,42 +473,263 @@ impl TransferInstruction{
             .ok_or(ProgramError::InvalidInstructionData)?;
         match tag {
             0 => {
-                let (start_time, rest) = rest.split_at(8);
-                let (amount_to_send, _rest) = rest.split_at(8);
-                msg!("start time byte: {:?}", start_time);
-                msg!("amount byte: {:?}", amount_to_send);
+                let (start_time, rest) = Self::unpack_u64(rest)?;
+                let (amount_to_send, rest) = Self::unpack_u64(rest)?;
+                let (nonce, rest) = Self::unpack_u64(rest)?;
+                let (lock_duration, mut rest) = Self::unpack_u64(rest)?;
+                msg!("start time: {:?}", start_time);
+                msg!("amount: {:?}", amount_to_send);
 
-                let start_time = start_time.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
-                let amount_to_send = amount_to_send.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
+                let schedule = Self::unpack_optional(&mut rest, Schedule::Cliff)?;
+                let hashlock = Self::unpack_optional(&mut rest, None)?;
+                let arbiter = Self::unpack_optional(&mut rest, None)?;
+                let approvals = Self::unpack_optional(&mut rest, None)?;
+                let release_authority = Self::unpack_optional(&mut rest, None)?;
+                let oracle_condition = Self::unpack_optional(&mut rest, None)?;
                 
-                Ok(TransferInstruction::CreateTranfer(TransferInput{start_time, amount_to_send}))
+                Ok(TransferInstruction::CreateTranfer(TransferInput{start_time, amount_to_send, nonce, lock_duration, schedule, hashlock, arbiter, approvals, release_authority, oracle_condition}))
             },
             1 =>{
-                let (amount, _rest) = rest.split_at(8);
-
-                let amount = amount.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
+                let (amount, preimage) = Self::unpack_u64(rest)?;
 
-                Ok(TransferInstruction::Withdraw(WithdrawInput{amount}))
+                Ok(TransferInstruction::Withdraw(WithdrawInput{amount, preimage: preimage.to_vec()}))
             },
             2 => {
-                let (start_time, rest) = rest.split_at(8);
-                let (amount, _rest) = rest.split_at(8);
+                let (start_time, rest) = Self::unpack_u64(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (nonce, rest) = Self::unpack_u64(rest)?;
+                let (lock_duration, mut rest) = Self::unpack_u64(rest)?;
                 
-                let start_time = start_time.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
-                let amount = amount.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
+                let schedule = Self::unpack_optional(&mut rest, Schedule::Cliff)?;
+                let approvals = Self::unpack_optional(&mut rest, None)?;
+                let oracle_condition = Self::unpack_optional(&mut rest, None)?;
+                let ui_amount = Self::unpack_optional(&mut rest, None)?;
+                let arbiter = Self::unpack_optional(&mut rest, None)?;
 
-                Ok(TransferInstruction::TransferToken(InitTokenInput{start_time , amount}))
+                Ok(TransferInstruction::TransferToken(InitTokenInput{start_time , amount, nonce, lock_duration, schedule, approvals, oracle_condition, ui_amount, arbiter}))
             }
 
             3 => {
-                let (amount, _rest) = rest.split_at(8);
-                
-                let amount = amount.try_into().map(u64::from_le_bytes).or(Err(program_error::INVALID_INSTRUCTION_DATA))?;
+                let (amount, mut rest) = Self::unpack_u64(rest)?;
+                let ui_amount = Self::unpack_optional(&mut rest, None)?;
 
-                Ok(TransferInstruction::WithdrawToken(WithdrawTokenInput{amount}))
+                Ok(TransferInstruction::WithdrawToken(WithdrawTokenInput{amount, ui_amount}))
             }
+
+            4 => Ok(TransferInstruction::Cancel),
+
+            5 => Ok(TransferInstruction::CancelToken),
+
+            6 | 7 => {
+                let (min_lock_duration, rest) = Self::unpack_u64(rest)?;
+                let (max_lock_duration, _rest) = Self::unpack_u64(rest)?;
+
+
+                let input = ConfigInput{min_lock_duration, max_lock_duration};
+                if *tag == 6 {
+                    Ok(TransferInstruction::InitConfig(input))
+                } else {
+                    Ok(TransferInstruction::UpdateConfig(input))
+                }
+            }
+            8 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+
+                Ok(TransferInstruction::TopUp(TopUpInput{amount}))
+            }
+
+            9 => {
+                let (amount, mut rest) = Self::unpack_u64(rest)?;
+                let ui_amount = Self::unpack_optional(&mut rest, None)?;
+
+                Ok(TransferInstruction::TopUpToken(TopUpTokenInput{amount, ui_amount}))
+            }
+
+            10 => Ok(TransferInstruction::StopStream),
+
+            11 => Ok(TransferInstruction::StopStreamToken),
+
+            12 | 13 => {
+                let (end_time, rest) = Self::unpack_u64(rest)?;
+                let (rate, _rest) = Self::unpack_u64(rest)?;
+
+
+                let input = UpdateScheduleInput{end_time, rate};
+                if *tag == 12 {
+                    Ok(TransferInstruction::UpdateSchedule(input))
+                } else {
+                    Ok(TransferInstruction::UpdateScheduleToken(input))
+                }
+            }
+
+            14 => {
+                let input = SplitInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::CreateSplit(input))
+            }
+
+            15 => Ok(TransferInstruction::WithdrawSplit),
+
+            16 => {
+                let input = BatchTransferInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::BatchCreateTransfer(input))
+            }
+
+            17 => {
+                let input = DistributorInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::CreateDistributor(input))
+            }
+
+            18 => {
+                let input = ClaimInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::ClaimDistribution(input))
+            }
+
+            19 => Ok(TransferInstruction::RaiseDispute),
+
+            20 => {
+                let input = ResolveDisputeInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::ResolveDispute(input))
+            }
+
+            21 => Ok(TransferInstruction::Approve),
+
+            22 => Ok(TransferInstruction::ApproveToken),
+
+            23 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (expiry, _rest) = Self::unpack_u64(rest)?;
+
+
+                Ok(TransferInstruction::AuthorizedRelease(ReleaseInput{amount, expiry}))
+            }
+
+            24 => {
+                let input = SwapInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::CreateSwap(input))
+            }
+
+            25 => Ok(TransferInstruction::TakeSwap),
+
+            26 => Ok(TransferInstruction::CancelSwap),
+
+            27 => {
+                let input = NftTransferInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::TransferNft(input))
+            }
+
+            28 => {
+                let input = BasketInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::CreateBasket(input))
+            }
+
+            29 => {
+                let input = WithdrawBasketInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::WithdrawBasket(input))
+            }
+
+            30 => Ok(TransferInstruction::CancelBasket),
+
+            31 => Ok(TransferInstruction::RaiseDisputeToken),
+
+            32 => {
+                let input = ResolveDisputeInput::try_from_slice(rest).or(Err(ProgramError::InvalidInstructionData))?;
+
+                Ok(TransferInstruction::ResolveDisputeToken(input))
+            }
+
+            33 => Ok(TransferInstruction::ClawbackDistribution),
+
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 
-}
\ No newline at end of file
+    /// Reads a little endian `u64` off the front of `input`, failing on short data
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        let value = input
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        Ok((value, &input[8..]))
+    }
+
+    /// Reads an optional borsh encoded trailing field, `default` when the data ends before it
+    fn unpack_optional<T: BorshDeserialize>(rest: &mut &[u8], default: T) -> Result<T, ProgramError> {
+        if rest.is_empty() {
+            return Ok(default);
+        }
+        T::deserialize(rest).or(Err(ProgramError::InvalidInstructionData))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::UiAmount;
+    use borsh::BorshSerialize;
+
+    #[test]
+    fn test_unpack_rejects_short_fixed_fields() {
+        // tag and the length of its fixed little endian fields
+        for (tag, len) in [(0u8, 32), (1, 8), (2, 32), (3, 8), (6, 16), (7, 16), (8, 8), (9, 8), (12, 16), (13, 16), (23, 16)] {
+            for short in 0..len {
+                let mut data = vec![tag];
+                data.resize(1 + short, 0);
+                assert_eq!(
+                    TransferInstruction::unpack(&data).err(),
+                    Some(ProgramError::InvalidInstructionData),
+                    "tag {} with {} bytes",
+                    tag,
+                    short
+                );
+            }
+            let mut data = vec![tag];
+            data.resize(1 + len, 0);
+            assert!(TransferInstruction::unpack(&data).is_ok(), "tag {}", tag);
+        }
+    }
+
+    #[test]
+    fn test_unpack_withdraw_preimage() {
+        let mut data = vec![1];
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(b"secret");
+        match TransferInstruction::unpack(&data) {
+            Ok(TransferInstruction::Withdraw(input)) => {
+                assert_eq!(input, WithdrawInput { amount: 7, preimage: b"secret".to_vec() });
+            }
+            _ => panic!("expected a withdraw"),
+        }
+    }
+
+    #[test]
+    fn test_unpack_top_up_token_ui_amount() {
+        let mut data = vec![9];
+        data.extend_from_slice(&7u64.to_le_bytes());
+        match TransferInstruction::unpack(&data) {
+            Ok(TransferInstruction::TopUpToken(input)) => {
+                assert_eq!(input, TopUpTokenInput { amount: 7, ui_amount: None });
+            }
+            _ => panic!("expected a token top up"),
+        }
+
+        let ui_amount = UiAmount { amount: "1.5".to_string(), decimals: 6 };
+        Some(ui_amount.clone()).serialize(&mut data).unwrap();
+        match TransferInstruction::unpack(&data) {
+            Ok(TransferInstruction::TopUpToken(input)) => {
+                assert_eq!(input, TopUpTokenInput { amount: 7, ui_amount: Some(ui_amount) });
+            }
+            _ => panic!("expected a token top up"),
+        }
+    }
+}
@@ -3,6 +3,124 @@ pub mod instruction;
 pub mod processor;
 pub mod state;
 pub mod error;
+pub mod merkle;
+pub mod ed25519;
+pub mod oracle;
+pub mod token;
+pub mod metadata;
+
+use solana_program::pubkey::Pubkey;
 
 pub const PREFIX: &str = "sol";
-pub const PREFIX_TOKEN: &str = "token";
\ No newline at end of file
+pub const PREFIX_TOKEN: &str = "token";
+pub const PREFIX_VAULT: &str = "vault";
+pub const PREFIX_CONFIG: &str = "config";
+pub const PREFIX_SPLIT: &str = "split";
+pub const PREFIX_DISTRIBUTOR: &str = "distributor";
+pub const PREFIX_CLAIM: &str = "claim";
+pub const PREFIX_SWAP: &str = "swap";
+pub const PREFIX_BASKET: &str = "basket";
+
+/// Derives the native escrow account address for a sender, receiver and nonce
+pub fn find_escrow_address(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    receiver: &Pubkey,
+    nonce: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            PREFIX.as_bytes(),
+            &sender.to_bytes(),
+            &receiver.to_bytes(),
+            &nonce.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the token escrow account address for a sender, receiver and nonce
+pub fn find_token_escrow_address(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    receiver: &Pubkey,
+    nonce: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            PREFIX_TOKEN.as_bytes(),
+            &sender.to_bytes(),
+            &receiver.to_bytes(),
+            &nonce.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the split escrow account address for a sender and nonce
+pub fn find_split_escrow_address(program_id: &Pubkey, sender: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_SPLIT.as_bytes(), &sender.to_bytes(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Derives the merkle distributor account address for a sender and nonce
+pub fn find_distributor_address(program_id: &Pubkey, sender: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_DISTRIBUTOR.as_bytes(), &sender.to_bytes(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Derives the account recording that a receiver claimed from a distributor
+pub fn find_claim_status_address(
+    program_id: &Pubkey,
+    distributor: &Pubkey,
+    receiver: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_CLAIM.as_bytes(), &distributor.to_bytes(), &receiver.to_bytes()],
+        program_id,
+    )
+}
+
+/// Derives the basket escrow account address for a sender, receiver and nonce
+pub fn find_basket_address(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    receiver: &Pubkey,
+    nonce: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            PREFIX_BASKET.as_bytes(),
+            &sender.to_bytes(),
+            &receiver.to_bytes(),
+            &nonce.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the token swap account address for an initializer and nonce
+pub fn find_swap_address(program_id: &Pubkey, initializer: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_SWAP.as_bytes(), &initializer.to_bytes(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Derives the vault holding the funds of an escrow, for token escrows the tokens
+/// sit in the vault's associated token account
+pub fn find_vault_address(program_id: &Pubkey, escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_VAULT.as_bytes(), &escrow.to_bytes()],
+        program_id,
+    )
+}
+
+/// Derives the program wide config account holding the allowed lock durations
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFIX_CONFIG.as_bytes()], program_id)
+}
@@ -0,0 +1,77 @@
+//! Merkle proofs for claim distributions
+
+use solana_program::{keccak, pubkey::Pubkey};
+
+/// Leaf of the distribution tree paying `amount` to `receiver`
+pub fn distribution_leaf(receiver: &Pubkey, amount: u64) -> [u8; 32] {
+    keccak::hashv(&[&receiver.to_bytes(), &amount.to_le_bytes()]).0
+}
+
+/// Checks `leaf` belongs to the tree with `root`, each pair of nodes is hashed in sorted
+/// order so proofs only need the sibling hashes
+pub fn verify_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).0
+        } else {
+            keccak::hashv(&[sibling, &node]).0
+        }
+    });
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[&a, &b]).0
+        } else {
+            keccak::hashv(&[&b, &a]).0
+        }
+    }
+
+    #[test]
+    fn test_distribution_leaf() {
+        let receiver = Pubkey::new_unique();
+        let mut preimage = receiver.to_bytes().to_vec();
+        preimage.extend_from_slice(&5u64.to_le_bytes());
+        assert_eq!(distribution_leaf(&receiver, 5), keccak::hash(&preimage).0);
+        assert_ne!(distribution_leaf(&receiver, 5), distribution_leaf(&receiver, 6));
+        assert_ne!(distribution_leaf(&receiver, 5), distribution_leaf(&Pubkey::new_unique(), 5));
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let leaves: Vec<[u8; 32]> = (1..=4)
+            .map(|amount| distribution_leaf(&Pubkey::new_unique(), amount))
+            .collect();
+        let left = parent(leaves[0], leaves[1]);
+        let right = parent(leaves[2], leaves[3]);
+        let root = parent(left, right);
+
+        assert!(verify_proof(&[leaves[1], right], &root, leaves[0]));
+        assert!(verify_proof(&[leaves[0], right], &root, leaves[1]));
+        assert!(verify_proof(&[leaves[3], left], &root, leaves[2]));
+        assert!(verify_proof(&[leaves[2], left], &root, leaves[3]));
+
+        // a single leaf tree is its own root
+        assert!(verify_proof(&[], &leaves[0], leaves[0]));
+    }
+
+    #[test]
+    fn test_verify_proof_rejects_wrong_leaf_or_proof() {
+        let leaves: Vec<[u8; 32]> = (1..=4)
+            .map(|amount| distribution_leaf(&Pubkey::new_unique(), amount))
+            .collect();
+        let left = parent(leaves[0], leaves[1]);
+        let right = parent(leaves[2], leaves[3]);
+        let root = parent(left, right);
+
+        assert!(!verify_proof(&[leaves[1], right], &root, leaves[2]));
+        assert!(!verify_proof(&[right, leaves[1]], &root, leaves[0]));
+        assert!(!verify_proof(&[leaves[1]], &root, leaves[0]));
+        assert!(!verify_proof(&[leaves[1], right], &left, leaves[0]));
+    }
+}
@@ -0,0 +1,174 @@
+//! Token metadata account layout, read to check the collection or creators of an NFT
+
+use borsh::BorshDeserialize;
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::EscrowError;
+
+pub mod metadata_program {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+/// Derives the metadata account of a mint
+pub fn find_metadata_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"metadata", &metadata_program::id().to_bytes(), &mint.to_bytes()],
+        &metadata_program::id(),
+    )
+}
+
+#[derive(BorshDeserialize, Debug)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+#[derive(BorshDeserialize, Debug)]
+pub struct Collection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+/// Fields of the metadata account the escrow cares about
+#[derive(Debug)]
+pub struct Metadata {
+    pub mint: Pubkey,
+    pub creators: Vec<Creator>,
+    pub collection: Option<Collection>,
+}
+
+impl Metadata {
+    /// Reads the fields in account order, skipping the ones in between.
+    /// Accounts written before collections existed are zero padded, which reads as `None`.
+    pub fn unpack(mut data: &[u8]) -> Result<Self, ProgramError> {
+        let data = &mut data;
+        let _key = u8::deserialize(data)?;
+        let _update_authority = Pubkey::deserialize(data)?;
+        let mint = Pubkey::deserialize(data)?;
+        let _name = String::deserialize(data)?;
+        let _symbol = String::deserialize(data)?;
+        let _uri = String::deserialize(data)?;
+        let _seller_fee_basis_points = u16::deserialize(data)?;
+        let creators = Option::<Vec<Creator>>::deserialize(data)?.unwrap_or_default();
+        let _primary_sale_happened = bool::deserialize(data)?;
+        let _is_mutable = bool::deserialize(data)?;
+        let _edition_nonce = Option::<u8>::deserialize(data)?;
+        let _token_standard = Option::<u8>::deserialize(data)?;
+        let collection = Option::<Collection>::deserialize(data)?;
+        Ok(Metadata { mint, creators, collection })
+    }
+
+    pub fn has_verified_collection(&self, collection: &Pubkey) -> bool {
+        matches!(&self.collection, Some(c) if c.verified && c.key == *collection)
+    }
+
+    pub fn has_verified_creator(&self, creator: &Pubkey) -> bool {
+        self.creators.iter().any(|c| c.verified && c.address == *creator)
+    }
+
+    /// Checks `metadata_info` is the metadata account of `mint` and lists the expected
+    /// verified collection and creator
+    pub fn assert_matches(
+        metadata_info: &AccountInfo,
+        mint: &Pubkey,
+        collection: Option<&Pubkey>,
+        creator: Option<&Pubkey>,
+    ) -> Result<(), ProgramError> {
+        if *metadata_info.owner != metadata_program::id() || *metadata_info.key != find_metadata_address(mint).0 {
+            return Err(EscrowError::InvalidMetadataAccount.into());
+        }
+        let metadata = Metadata::unpack(&metadata_info.data.borrow())
+            .map_err(|_| EscrowError::InvalidMetadataAccount)?;
+        if metadata.mint != *mint {
+            return Err(EscrowError::InvalidMetadataAccount.into());
+        }
+        if collection.is_some_and(|key| !metadata.has_verified_collection(key))
+            || creator.is_some_and(|key| !metadata.has_verified_creator(key))
+        {
+            return Err(EscrowError::MetadataMismatch.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Size the metadata program allocates for every metadata account
+    const MAX_METADATA_LEN: usize = 679;
+
+    /// Name, symbol and uri are stored padded with NUL characters to their maximum length
+    fn puffed_string(data: &mut Vec<u8>, value: &str, len: usize) {
+        data.extend_from_slice(&(len as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data.resize(data.len() + len - value.len(), 0);
+    }
+
+    /// A `MetadataV1` account as written by the metadata program, up to `is_mutable`
+    fn legacy_metadata(mint: &Pubkey, creators: &[(Pubkey, bool, u8)]) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(&Pubkey::new_unique().to_bytes());
+        data.extend_from_slice(&mint.to_bytes());
+        puffed_string(&mut data, "Escrowed NFT", 32);
+        puffed_string(&mut data, "ENFT", 10);
+        puffed_string(&mut data, "https://example.com/nft.json", 200);
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&(creators.len() as u32).to_le_bytes());
+        for (address, verified, share) in creators {
+            data.extend_from_slice(&address.to_bytes());
+            data.push(*verified as u8);
+            data.push(*share);
+        }
+        data.push(1); // primary_sale_happened
+        data.push(1); // is_mutable
+        data
+    }
+
+    #[test]
+    fn test_unpack_metadata_with_collection() {
+        let mint = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let unverified = Pubkey::new_unique();
+        let collection = Pubkey::new_unique();
+
+        let mut data = legacy_metadata(&mint, &[(creator, true, 60), (unverified, false, 40)]);
+        data.extend_from_slice(&[1, 254]); // edition_nonce
+        data.extend_from_slice(&[1, 0]); // token_standard, NonFungible
+        data.push(1);
+        data.push(1);
+        data.extend_from_slice(&collection.to_bytes());
+        data.push(0); // uses
+        data.push(0); // collection_details
+        data.push(0); // programmable_config
+        data.resize(MAX_METADATA_LEN, 0);
+
+        let metadata = Metadata::unpack(&data).unwrap();
+        assert_eq!(metadata.mint, mint);
+        assert_eq!(metadata.creators.len(), 2);
+        assert!(metadata.has_verified_creator(&creator));
+        assert!(!metadata.has_verified_creator(&unverified));
+        assert!(metadata.has_verified_collection(&collection));
+        assert!(!metadata.has_verified_collection(&Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn test_unpack_metadata_before_collections() {
+        let mint = Pubkey::new_unique();
+        let mut data = legacy_metadata(&mint, &[]);
+        data.resize(MAX_METADATA_LEN, 0);
+
+        let metadata = Metadata::unpack(&data).unwrap();
+        assert_eq!(metadata.mint, mint);
+        assert!(metadata.creators.is_empty());
+        assert!(metadata.collection.is_none());
+    }
+
+    #[test]
+    fn test_unpack_metadata_rejects_truncated_data() {
+        let data = legacy_metadata(&Pubkey::new_unique(), &[]);
+        assert!(Metadata::unpack(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Data feed parsers for oracle conditioned escrows
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+
+use crate::error::EscrowError;
+
+/// How an oracle account stores its value, each supported feed format is a variant
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum OracleLayout {
+    /// Simple local test oracle, a little endian `i64` at the start of the account data
+    Test,
+}
+
+impl OracleLayout {
+    /// Reads the current feed value from the oracle account data
+    pub fn read_value(&self, data: &[u8]) -> Result<i64, ProgramError> {
+        match self {
+            OracleLayout::Test => data
+                .get(..8)
+                .and_then(|value| value.try_into().ok())
+                .map(i64::from_le_bytes)
+                .ok_or_else(|| EscrowError::InvalidOracleAccount.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_reads_leading_i64() {
+        let mut data = (-42i64).to_le_bytes().to_vec();
+        data.extend_from_slice(&[0xff; 8]);
+        assert_eq!(OracleLayout::Test.read_value(&data), Ok(-42));
+        assert_eq!(OracleLayout::Test.read_value(&i64::MAX.to_le_bytes()), Ok(i64::MAX));
+    }
+
+    #[test]
+    fn test_layout_rejects_short_data() {
+        assert_eq!(
+            OracleLayout::Test.read_value(&[1; 7]),
+            Err(EscrowError::InvalidOracleAccount.into())
+        );
+        assert_eq!(
+            OracleLayout::Test.read_value(&[]),
+            Err(EscrowError::InvalidOracleAccount.into())
+        );
+    }
+}
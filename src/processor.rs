@@ -1,11 +1,17 @@
-use spl_associated_token_account;
-
 use crate::{
     instruction::TransferInstruction,
-    state::{TransferInput, WithdrawInput, Escrow, InitTokenInput, WithdrawTokenInput, TransferToken},
+    state::{TransferInput, WithdrawInput, Escrow, InitTokenInput, WithdrawTokenInput, TransferToken, Config, ConfigInput, Schedule, TopUpInput, TopUpTokenInput, UpdateScheduleInput, SplitInput, SplitEscrow, SplitRecipient, BatchTransferInput, DistributorInput, ClaimInput, MerkleDistributor, ClaimStatus, ResolveDisputeInput, ReleaseInput, OracleCondition, SwapInput, SwapEscrow, UiAmount, NftTransferInput, BasketInput, BasketEscrow, BasketLeg, BasketLegId, WithdrawBasketInput},
 };
 
-use crate::{PREFIX,PREFIX_TOKEN};
+use crate::{
+    find_basket_address, find_claim_status_address, find_config_address, find_distributor_address, find_escrow_address,
+    find_split_escrow_address, find_swap_address, find_token_escrow_address, find_vault_address,
+    ed25519::assert_release_authorized,
+    merkle::{distribution_leaf, verify_proof},
+    metadata::Metadata,
+    token,
+    PREFIX, PREFIX_BASKET, PREFIX_CLAIM, PREFIX_CONFIG, PREFIX_DISTRIBUTOR, PREFIX_SPLIT, PREFIX_SWAP, PREFIX_TOKEN, PREFIX_VAULT,
+};
 use super::error::{TokenError, EscrowError};
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -13,15 +19,52 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::Clock,
     entrypoint::ProgramResult,
+    hash::hash,
     program_error::ProgramError,
     pubkey::Pubkey,
-    msg, program_pack::Pack, program::{invoke_signed, invoke},
+    msg, program::{invoke_signed, invoke},
     system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{instructions::{load_current_index_checked, load_instruction_at_checked}, rent::Rent, Sysvar},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
 };
 
 pub struct Processor;
 
+/// Accounts of `TransferToken`, `TransferNft` expects the same ones first
+struct TokenTransferAccounts<'a, 'b> {
+    escrow_account: &'a AccountInfo<'b>,
+    sender_account: &'a AccountInfo<'b>,
+    receiver_account: &'a AccountInfo<'b>,
+    system_program: &'a AccountInfo<'b>,
+    token_mint_info: &'a AccountInfo<'b>,
+    token_program_info: &'a AccountInfo<'b>,
+    sender_associated_info: &'a AccountInfo<'b>,
+    vault_associated_info: &'a AccountInfo<'b>,
+    rent_info: &'a AccountInfo<'b>,
+    associated_token_info: &'a AccountInfo<'b>,
+    vault: &'a AccountInfo<'b>,
+    config_account: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> TokenTransferAccounts<'a, 'b> {
+    fn next<I: Iterator<Item = &'a AccountInfo<'b>>>(account_info_iter: &mut I) -> Result<Self, ProgramError> {
+        Ok(TokenTransferAccounts {
+            escrow_account: next_account_info(account_info_iter)?, // pda data storage
+            sender_account: next_account_info(account_info_iter)?, //sender
+            receiver_account: next_account_info(account_info_iter)?, // receiver
+            system_program: next_account_info(account_info_iter)?,  // system program
+            token_mint_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?, // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+            sender_associated_info: next_account_info(account_info_iter)?,
+            vault_associated_info: next_account_info(account_info_iter)?,
+            rent_info: next_account_info(account_info_iter)?,
+            associated_token_info: next_account_info(account_info_iter)?, // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+            vault: next_account_info(account_info_iter)?,
+            config_account: next_account_info(account_info_iter)?,
+        })
+    }
+}
+
 impl Processor{
     pub fn process(
         program_id: &Pubkey,
@@ -34,30 +77,119 @@ impl Processor{
 
         msg!("unpacking done");
         match instruction {
-            TransferInstruction::CreateTranfer(TransferInput { start_time, amount_to_send }) => {
-                Self::process_create_transfer(program_id, accounts, start_time, amount_to_send)
+            TransferInstruction::CreateTranfer(input) => {
+                Self::process_create_transfer(program_id, accounts, input)
             }
-            TransferInstruction::Withdraw(WithdrawInput{amount}) => {
-                Self::process_withdraw(program_id, accounts, amount)
+            TransferInstruction::Withdraw(WithdrawInput{amount, preimage}) => {
+                Self::process_withdraw(program_id, accounts, amount, &preimage)
             },
-            TransferInstruction::TransferToken(InitTokenInput{start_time, amount}) => {
-                Self::process_create_token_transfer(program_id, accounts, start_time, amount)
+            TransferInstruction::TransferToken(input) => {
+                let account_info_iter = &mut accounts.iter();
+                let transfer_accounts = TokenTransferAccounts::next(account_info_iter)?;
+                Self::process_create_token_transfer(program_id, &transfer_accounts, input)
             },
-            TransferInstruction::WithdrawToken(WithdrawTokenInput{amount}) => {
-                Self::process_withdraw_token(program_id, accounts, amount)
-            }      
+            TransferInstruction::WithdrawToken(WithdrawTokenInput{amount, ui_amount}) => {
+                Self::process_withdraw_token(program_id, accounts, amount, ui_amount)
+            }
+            TransferInstruction::Cancel => {
+                Self::process_cancel(program_id, accounts)
+            }
+            TransferInstruction::CancelToken => {
+                Self::process_cancel_token(program_id, accounts)
+            }
+            TransferInstruction::InitConfig(input) => {
+                Self::process_init_config(program_id, accounts, input)
+            }
+            TransferInstruction::UpdateConfig(input) => {
+                Self::process_update_config(program_id, accounts, input)
+            }
+            TransferInstruction::TopUp(TopUpInput{amount}) => {
+                Self::process_top_up(program_id, accounts, amount)
+            }
+            TransferInstruction::TopUpToken(TopUpTokenInput{amount, ui_amount}) => {
+                Self::process_top_up_token(program_id, accounts, amount, ui_amount)
+            }
+            TransferInstruction::StopStream => {
+                Self::process_stop_stream(program_id, accounts)
+            }
+            TransferInstruction::StopStreamToken => {
+                Self::process_stop_stream_token(program_id, accounts)
+            }
+            TransferInstruction::UpdateSchedule(input) => {
+                Self::process_update_schedule(program_id, accounts, input)
+            }
+            TransferInstruction::UpdateScheduleToken(input) => {
+                Self::process_update_schedule_token(program_id, accounts, input)
+            }
+            TransferInstruction::CreateSplit(input) => {
+                Self::process_create_split(program_id, accounts, input)
+            }
+            TransferInstruction::WithdrawSplit => {
+                Self::process_withdraw_split(program_id, accounts)
+            }
+            TransferInstruction::BatchCreateTransfer(input) => {
+                Self::process_batch_create_transfer(program_id, accounts, input)
+            }
+            TransferInstruction::CreateDistributor(input) => {
+                Self::process_create_distributor(program_id, accounts, input)
+            }
+            TransferInstruction::ClaimDistribution(input) => {
+                Self::process_claim_distribution(program_id, accounts, input)
+            }
+            TransferInstruction::RaiseDispute => {
+                Self::process_raise_dispute(program_id, accounts)
+            }
+            TransferInstruction::ResolveDispute(input) => {
+                Self::process_resolve_dispute(program_id, accounts, input)
+            }
+            TransferInstruction::Approve => {
+                Self::process_approve(program_id, accounts)
+            }
+            TransferInstruction::ApproveToken => {
+                Self::process_approve_token(program_id, accounts)
+            }
+            TransferInstruction::AuthorizedRelease(input) => {
+                Self::process_authorized_release(program_id, accounts, input)
+            }
+            TransferInstruction::CreateSwap(input) => {
+                Self::process_create_swap(program_id, accounts, input)
+            }
+            TransferInstruction::TakeSwap => {
+                Self::process_take_swap(program_id, accounts)
+            }
+            TransferInstruction::CancelSwap => {
+                Self::process_cancel_swap(program_id, accounts)
+            }
+            TransferInstruction::TransferNft(input) => {
+                Self::process_transfer_nft(program_id, accounts, input)
+            }
+            TransferInstruction::CreateBasket(input) => {
+                Self::process_create_basket(program_id, accounts, input)
+            }
+            TransferInstruction::WithdrawBasket(input) => {
+                Self::process_withdraw_basket(program_id, accounts, input)
+            }
+            TransferInstruction::CancelBasket => {
+                Self::process_cancel_basket(program_id, accounts)
+            }
+            TransferInstruction::RaiseDisputeToken => {
+                Self::process_raise_dispute_token(program_id, accounts)
+            }
+            TransferInstruction::ResolveDisputeToken(input) => {
+                Self::process_resolve_dispute_token(program_id, accounts, input)
+            }
+            TransferInstruction::ClawbackDistribution => {
+                Self::process_clawback_distribution(program_id, accounts)
+            }
         }
     }
 
     fn process_create_transfer(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        start_time: u64, 
-        amount_to_send: u64
+        input: TransferInput,
     ) -> ProgramResult {
-
-        // Get the rent sysvar via syscall
-        // let rent = Rent::get()?; //
+        let TransferInput { start_time, amount_to_send, nonce, lock_duration, schedule, hashlock, arbiter, approvals, release_authority, oracle_condition } = input;
         
         msg!("INTO CREATE TRANSFER NATIVE!");
         msg!("start: {:?}", start_time);
@@ -70,68 +202,144 @@ impl Processor{
         let receiver_account = next_account_info(account_info_iter)?;
         let system_program = next_account_info(account_info_iter)?;    
         let vault = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
 
         if !sender_account.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let (_account_address, bump_seed) = Pubkey::find_program_address(
-            &[&sender_account.key.to_bytes()],
+        Self::load_config(program_id, config_account)?.check_lock_duration(lock_duration)?;
+
+        let escrow = Escrow {
+            is_initialized: true,
+            start_time,
+            receiver: *receiver_account.key,
+            amount_to_send,
+            sender: *sender_account.key,
+            withdrawn: 0,
+            nonce,
+            lock_duration,
+            schedule,
+            hashlock,
+            arbiter,
+            disputed: false,
+            approvals,
+            release_authority,
+            oracle_condition,
+        };
+        Self::create_escrow(program_id, escrow_account, sender_account, system_program, vault, escrow)?;
+        
+        msg!("COMPLETED");
+
+        Ok(())
+    }
+
+    /// Creates a native escrow PDA holding `escrow` and funds its vault from the sender
+    fn create_escrow<'a>(
+        program_id: &Pubkey,
+        escrow_account: &AccountInfo<'a>,
+        sender_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        escrow: Escrow,
+    ) -> ProgramResult {
+        escrow.schedule.validate(escrow.start_time, escrow.amount_to_send)?;
+        if escrow.hashlock.is_some() && escrow.schedule != Schedule::Cliff {
+            return Err(EscrowError::InvalidSchedule.into());
+        }
+        if let Some(approvals) = &escrow.approvals {
+            approvals.validate()?;
+        }
+
+        let (escrow_address, bump_seed) = find_escrow_address(
             program_id,
+            &escrow.sender,
+            &escrow.receiver,
+            escrow.nonce,
         );
+        if escrow_address != *escrow_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, escrow_account, vault)?;
+
         let pda_signer_seeds: &[&[_]] = &[
-            &sender_account.key.to_bytes(),
+            PREFIX.as_bytes(),
+            &escrow.sender.to_bytes(),
+            &escrow.receiver.to_bytes(),
+            &escrow.nonce.to_le_bytes(),
             &[bump_seed],
         ];
 
+        msg!("escrow sender confirm {:?}", escrow.sender);
+        msg!("escrow amount to send confirm {:?}", escrow.amount_to_send);
+
+        // the schedule makes the escrow variable sized, the account is sized to fit it exactly
+        let escrow_len = escrow.try_to_vec()?.len();
+
         msg!("Creating Escrow Account");
-        invoke_signed(
-            &system_instruction::create_account(
-                sender_account.key, 
-                escrow_account.key, 
-                Rent::get()?.minimum_balance(std::mem::size_of::<Escrow>()),
-                81,
-                program_id
-            ),
-            &[sender_account.clone(), escrow_account.clone(),system_program.clone()],
-            &[pda_signer_seeds]
+        Self::create_pda_account(
+            program_id,
+            sender_account,
+            escrow_account,
+            system_program,
+            escrow_len,
+            pda_signer_seeds,
         )?;
 
-        msg!("Escrow Buffer ====> {:?}" , &escrow_account.data.borrow());
-        msg!("Escrow Buffer length ====> {:?}" , &escrow_account.data_len());
+        msg!("packing escrow");
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
 
-        msg!("unpacking escrow");
-        let mut escrow = Escrow::try_from_slice(&escrow_account.data.borrow())?;
-        //let mut escrow = Escrow::unpack_unchecked(&escrow_account.try_borrow_mut_data()?)?;
+        Self::fund_native_vault(sender_account, vault, system_program, escrow.amount_to_send)?;
 
-        escrow.is_initialized = true;
-        escrow.start_time = start_time;
-        escrow.receiver = *receiver_account.key;
-        escrow.amount_to_send = amount_to_send;
-        escrow.sender = *sender_account.key;
+        Ok(())
+    }
 
-        msg!("escrow sender confirm {:?}", escrow.sender);
-        msg!("escrow amount to send confirm {:?}", escrow.amount_to_send);
+    fn process_batch_create_transfer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: BatchTransferInput,
+    ) -> ProgramResult {
+        msg!("INTO BATCH CREATE TRANSFER NATIVE!");
+        msg!("entries: {:?}", input.entries.len());
 
-        msg!("packing escrow");
-        //escrow::pack(escrow, &mut escrow_account.try_borrow_mut_data()?)?; 
-        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        let account_info_iter = &mut accounts.iter();
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
 
-        invoke_signed(
-            &system_instruction::transfer(
-                sender_account.key,
-                vault.key,
-                amount_to_send
-            ),
-            &[
-                sender_account.clone(),
-                vault.clone(),
-                system_program.clone()
-            ],
-            &[pda_signer_seeds],
-        )?;
-        
-        msg!("COMPLETED");
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if input.entries.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Self::load_config(program_id, config_account)?.check_lock_duration(input.lock_duration)?;
+
+        for entry in input.entries {
+            let escrow_account = next_account_info(account_info_iter)?;
+            let vault = next_account_info(account_info_iter)?;
+
+            let escrow = Escrow {
+                is_initialized: true,
+                start_time: entry.start_time,
+                receiver: entry.receiver,
+                amount_to_send: entry.amount,
+                sender: *sender_account.key,
+                withdrawn: 0,
+                nonce: entry.nonce,
+                lock_duration: input.lock_duration,
+                schedule: Schedule::Cliff,
+                hashlock: None,
+                arbiter: None,
+                disputed: false,
+                approvals: None,
+                release_authority: None,
+                oracle_condition: None,
+            };
+            Self::create_escrow(program_id, escrow_account, sender_account, system_program, vault, escrow)?;
+        }
 
         Ok(())
     }
@@ -140,6 +348,7 @@ impl Processor{
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        preimage: &[u8],
     ) -> ProgramResult{
         msg!("INTO PROCESS WITHDRAW NATIVE!!");
         msg!("amount {:?}", amount);
@@ -151,36 +360,57 @@ impl Processor{
         let system_program = next_account_info(account_info_iter)?;  //   system program     \\
         let vault = next_account_info(account_info_iter)?;          //     vault account      \\
 
-        let escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow()).expect("Failed to seriallize");
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
 
         if *receiver_account.key != escrow_data.receiver {
             return Err(ProgramError::IllegalOwner);
         }
 
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
         if !receiver_account.is_signer { 
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        // let (account_address, _bump_seed) = Pubkey::find_program_address(
-        //     &[&sender_account.key.to_bytes()],
-        //     program_id,
-        // );
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
 
-        // if account_address != *escrow_account.key{
-        //     return Err(ProgramError::InvalidAccountData);
-        // }
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
 
-        if escrow_data.start_time + 2 > Clock::get()?.unix_timestamp as u64{ // 24 hours not passed yet (24*60*60)
+        let now = Clock::get()?.unix_timestamp as u64;
+        if let Some(hashlock) = escrow_data.hashlock {
+            if now >= escrow_data.unlock_time()? { // only the sender can refund now
+                return Err(EscrowError::HashlockExpired.into());
+            }
+            if hash(preimage).to_bytes() != hashlock {
+                return Err(EscrowError::InvalidPreimage.into());
+            }
+        } else if escrow_data.unlock_time()? > now{ // lock duration not passed yet
             return Err(EscrowError::WithdrawTimeLimitNotExceed.into());
         }
+
+        if let Some(approvals) = &escrow_data.approvals {
+            approvals.assert_approved()?;
+        }
+
+        if let Some(condition) = &escrow_data.oracle_condition {
+            Self::assert_oracle_condition(condition, next_account_info(account_info_iter)?)?;
+        }
+
+        let amount = Self::withdraw_amount(&escrow_data.schedule, amount, escrow_data.withdrawable(now)?)?;
         
-        let (_account_address, bump_seed) = Pubkey::find_program_address(
-            &[&sender_account.key.to_bytes()],
-            program_id,
-        );
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
         let pda_signer_seeds: &[&[_]] = &[
-            &sender_account.key.to_bytes(),
-            &[bump_seed],
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
         ];
 
         invoke_signed(
@@ -196,155 +426,113 @@ impl Processor{
             ],
             &[pda_signer_seeds],
         )?;
+
+        escrow_data.withdrawn += amount;
+        escrow_data.schedule.claim(escrow_data.start_time, now);
+        msg!("withdrawn {:?} of {:?}", escrow_data.withdrawn, escrow_data.amount_to_send);
+
+        if escrow_data.withdrawn == escrow_data.amount_to_send {
+            msg!("escrow fully paid, closing");
+            Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
         Ok(())
     }
 
-    fn process_create_token_transfer(
+    fn process_cancel(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        start_time: u64,
-        amount: u64,
-    ) -> ProgramResult{
-
-        msg!("INTO CREATE TRANSFER SPL!");
-        msg!("start: {:?}", start_time);
-        msg!("amount: {:?}", amount);
+    ) -> ProgramResult {
+        msg!("INTO PROCESS CANCEL NATIVE!!");
 
         let account_info_iter = &mut accounts.iter();
-        let escrow_account = next_account_info(account_info_iter)?; // pda data storage
-        let sender_account = next_account_info(account_info_iter)?; //sender
-        let receiver_account = next_account_info(account_info_iter)?; // receiver
-        let system_program = next_account_info(account_info_iter)?;  // system program
-        let token_mint_info = next_account_info(account_info_iter)?; 
-        let token_program_info = next_account_info(account_info_iter)?; 
-        let sender_associated_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
-        let vault_associated_info = next_account_info(account_info_iter)?;
-        let rent_info = next_account_info(account_info_iter)?; 
-        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
         let vault = next_account_info(account_info_iter)?;
 
-        if token_program_info.key != &spl_token::id() {
-            return Err(ProgramError::IncorrectProgramId);
-        }    
-        // Since we are performing system_instruction source account must be signer
-        if !sender_account.is_signer {
-            return Err(ProgramError::MissingRequiredSignature); 
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
         }
 
-        let (_account_address, bump_seed) = Pubkey::find_program_address(
-            &[
-                PREFIX_TOKEN.as_bytes(),
-                &sender_account.key.to_bytes()
-                ],
-            program_id,
-        );
-
-        // if account_address != *escrow_account.key{
-        //     return Err(ProgramError::InvalidAccountData);
-        // }
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-        invoke_signed(
-            &system_instruction::create_account(
-                sender_account.key, 
-                escrow_account.key,
-                Rent::get()?.minimum_balance(std::mem::size_of::<InitTokenInput>()),
-                112,
-                program_id
-            ),
-            &[sender_account.clone(), escrow_account.clone(),system_program.clone()],
-            &[&[sender_account.key.as_ref(),&[bump_seed]]]
-        )?;
+        if escrow_account.data_is_empty(){
+            return Err(ProgramError::UninitializedAccount);
+        }
 
-        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
-        
-        escrow.start_time = start_time;
-        escrow.amount = amount;
-        escrow.token_mint = *token_mint_info.key;
-        escrow.sender = *sender_account.key;
-        escrow.receiver = *receiver_account.key;
+        let escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
 
-        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
 
-        //creating associated token program for receiver to transfer token
-        invoke(
-            &spl_associated_token_account::create_associated_token_account(
-                sender_account.key,
-                vault.key,
-                token_mint_info.key
-            ), 
-            &[
-                sender_account.clone(),
-                vault_associated_info.clone(),
-                vault.clone(),
-                token_mint_info.clone(),
-                system_program.clone(),
-                token_program_info.clone(),
-                rent_info.clone(),
-                associated_token_info.clone(),
-            ]
-        )?;
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
 
-        let (_account_address, bump) = Pubkey::find_program_address(
-            &[&sender_account.key.to_bytes()], 
-            program_id
-        );
+        let unlocked = escrow_data.unlock_time()? <= Clock::get()?.unix_timestamp as u64;
+        if escrow_data.hashlock.is_some() {
+            if !unlocked { // receiver may still reveal the preimage
+                return Err(EscrowError::RefundTimeoutNotReached.into());
+            }
+        } else if unlocked { // receiver can already withdraw
+            return Err(EscrowError::CancelTimeLimitExceeded.into());
+        }
 
-        let pda_signer_seeds: &[&[_]] = &[&sender_account.key.to_bytes(), &[bump]];
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
 
+        msg!("refunding {:?} lamports to sender", vault.lamports());
         invoke_signed(
-            &spl_token::instruction::transfer(
-                token_program_info.key,
-                sender_associated_info.key,
-                vault_associated_info.key,
+            &system_instruction::transfer(
+                vault.key,
                 sender_account.key,
-                &[sender_account.key],
-                amount,
-            )?,
+                vault.lamports()
+            ),
             &[
-                token_program_info.clone(),
-                vault_associated_info.clone(),
-                sender_associated_info.clone(),
+                vault.clone(),
                 sender_account.clone(),
-                system_program.clone(),
-            ],&[&pda_signer_seeds],
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
         )?;
 
-        Ok(())
+        Self::close_account(escrow_account, sender_account)
     }
 
-    fn process_withdraw_token(
+    fn process_cancel_token(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
     ) -> ProgramResult {
-
-        msg!("INTO PROCESS WITHDRAW SPL TOKEN!!");
-        msg!("amount {:?}", amount);
+        msg!("INTO PROCESS CANCEL SPL TOKEN!!");
 
         let account_info_iter = &mut accounts.iter();
         let escrow_account = next_account_info(account_info_iter)?;
         let sender_account = next_account_info(account_info_iter)?;
         let vault = next_account_info(account_info_iter)?;
-        let receiver_account = next_account_info(account_info_iter)?;
-        let system_program = next_account_info(account_info_iter)?; 
         let token_mint_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
-        let vault_associated_info = next_account_info(account_info_iter)?; 
-        let receiver_associated_info = next_account_info(account_info_iter)?;
-        let rent_info = next_account_info(account_info_iter)?; 
-        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
-
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let sender_associated_info = next_account_info(account_info_iter)?;
 
         if *escrow_account.owner != *program_id {
-            return Err(ProgramError::InvalidArgument);
+            return Err(EscrowError::InvalidEscrowOwner.into());
         }
 
-        if token_program_info.key != &spl_token::id() {
-            return Err(ProgramError::IncorrectProgramId);
-        }
+        token::assert_token_program(token_program_info, token_mint_info)?;
 
-        if !receiver_account.is_signer {
-            return Err(ProgramError::MissingRequiredSignature); 
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
         if escrow_account.data_is_empty(){
@@ -352,64 +540,2796 @@ impl Processor{
         }
 
         let escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
 
         if escrow.token_mint != *token_mint_info.key {
             return Err(TokenError::PublicKeyMismatch.into());
         }
 
-        if *receiver_account.key != escrow.receiver {
+        if *sender_account.key != escrow.sender {
             return Err(TokenError::EscrowMismatch.into());
         }
 
-        if escrow.start_time + 2 > Clock::get()?.unix_timestamp as u64{ // 24 hours not passed yet
-            return Err(EscrowError::WithdrawTimeLimitNotExceed.into());
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
         }
 
-        //creating associated token program for receiver to transfer token
-        invoke(
-            &spl_associated_token_account::create_associated_token_account(
-                receiver_account.key,
-                receiver_account.key,
-                token_mint_info.key
-            ), 
-            &[
-                receiver_account.clone(),
-                receiver_associated_info.clone(),
-                receiver_account.clone(),
-                token_mint_info.clone(),
-                system_program.clone(),
-                token_program_info.clone(),
-                rent_info.clone(),
-                associated_token_info.clone(),
-            ]
-        )?;
+        if escrow.unlock_time()? <= Clock::get()?.unix_timestamp as u64{ // receiver can already withdraw
+            return Err(EscrowError::CancelTimeLimitExceeded.into());
+        }
 
-        let (_account_address, bump) = Pubkey::find_program_address(
-            &[&sender_account.key.to_bytes()], 
-            program_id
-        );
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+        let vault_token_account = token::unpack_account(&vault_associated_info.data.borrow())?;
 
-        let pda_signer_seeds: &[&[_]] = &[&sender_account.key.to_bytes(), &[bump]];
-        
-        //transfering token to receiver_associated_info
-        invoke_signed(
-            &spl_token::instruction::transfer(
-                token_program_info.key,
-                vault_associated_info.key,
-                receiver_associated_info.key,
-                vault.key,
-                &[vault.key],
-                amount,
-            )?,
-            &[
-                token_program_info.clone(),
-                vault_associated_info.clone(),
-                receiver_associated_info.clone(),
-                vault.clone(),
-                system_program.clone()
-            ],&[&pda_signer_seeds],
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("refunding {:?} tokens to sender", vault_token_account.amount);
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            sender_associated_info,
+            vault,
+            vault_token_account.amount,
+            escrow.decimals,
+            pda_signer_seeds,
+        )?;
+
+        Self::close_token_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            sender_associated_info,
+            sender_account,
+            vault,
+            escrow.decimals,
+            pda_signer_seeds,
         )?;
+        Self::close_account(escrow_account, sender_account)
+    }
 
+    fn process_top_up(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS TOP UP NATIVE!!");
+        msg!("amount {:?}", amount);
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+        Self::assert_vault_address(program_id, escrow_account, vault)?;
+
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow_data.schedule.top_up(amount, escrow_data.amount_to_send, escrow_data.start_time, now)?;
+
+        invoke(
+            &system_instruction::transfer(
+                sender_account.key,
+                vault.key,
+                amount
+            ),
+            &[
+                sender_account.clone(),
+                vault.clone(),
+                system_program.clone()
+            ],
+        )?;
+
+        escrow_data.amount_to_send = escrow_data.amount_to_send
+            .checked_add(amount)
+            .ok_or(EscrowError::Overflow)?;
+        msg!("TOP UP: escrow {:?} amount {:?} total {:?}", escrow_account.key, amount, escrow_data.amount_to_send);
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_top_up_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        ui_amount: Option<UiAmount>,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS TOP UP SPL TOKEN!!");
+        msg!("amount {:?}", amount);
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let sender_associated_info = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+        Self::assert_vault_address(program_id, escrow_account, vault)?;
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if escrow.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+
+        if *sender_account.key != escrow.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let amount = Self::resolve_ui_amount(amount, ui_amount, escrow.decimals)?;
+
+        let balance_before = Self::vault_balance(vault_associated_info)?;
+        Self::transfer_tokens(
+            token_program_info,
+            sender_associated_info,
+            token_mint_info,
+            vault_associated_info,
+            sender_account,
+            amount,
+            escrow.decimals,
+            &[],
+        )?;
+        let received = Self::vault_balance(vault_associated_info)?
+            .checked_sub(balance_before)
+            .ok_or(EscrowError::Overflow)?;
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow.schedule.top_up(received, escrow.amount, escrow.start_time, now)?;
+        escrow.amount = escrow.amount
+            .checked_add(received)
+            .ok_or(EscrowError::Overflow)?;
+        msg!("TOP UP: escrow {:?} amount {:?} total {:?}", escrow_account.key, received, escrow.amount);
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_stop_stream(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS STOP STREAM NATIVE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow_data.schedule.stop(now)?;
+
+        // whatever accrued until now stays for the receiver, the rest is refunded
+        let streamed = escrow_data.schedule.released_amount(escrow_data.amount_to_send, escrow_data.start_time, now)?;
+        let refund = escrow_data.amount_to_send - streamed;
+        escrow_data.amount_to_send = streamed;
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("refunding {:?} lamports to sender", refund);
+        invoke_signed(
+            &system_instruction::transfer(
+                vault.key,
+                sender_account.key,
+                refund
+            ),
+            &[
+                vault.clone(),
+                sender_account.clone(),
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
+        )?;
+
+        if escrow_data.withdrawn == escrow_data.amount_to_send {
+            msg!("escrow fully paid, closing");
+            Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_stop_stream_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS STOP STREAM SPL TOKEN!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let sender_associated_info = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if escrow.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+
+        if *sender_account.key != escrow.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow.schedule.stop(now)?;
+
+        // whatever accrued until now stays for the receiver, the rest is refunded
+        let streamed = escrow.schedule.released_amount(escrow.amount, escrow.start_time, now)?;
+        let refund = escrow.amount - streamed;
+        escrow.amount = streamed;
+        Self::assert_vault_funded(vault_associated_info, escrow.amount - escrow.withdrawn + refund)?;
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("refunding {:?} tokens to sender", refund);
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            sender_associated_info,
+            vault,
+            refund,
+            escrow.decimals,
+            pda_signer_seeds,
+        )?;
+
+        if escrow.withdrawn == escrow.amount {
+            msg!("escrow fully paid, closing");
+            Self::close_token_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                sender_associated_info,
+                sender_account,
+                vault,
+                escrow.decimals,
+                pda_signer_seeds,
+            )?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_update_schedule(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: UpdateScheduleInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS UPDATE SCHEDULE NATIVE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow_data.schedule.update(
+            &input,
+            escrow_data.amount_to_send,
+            escrow_data.start_time,
+            escrow_data.withdrawn,
+            now,
+        )?;
+        msg!("UPDATE SCHEDULE: escrow {:?} schedule {:?}", escrow_account.key, escrow_data.schedule);
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_update_schedule_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: UpdateScheduleInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS UPDATE SCHEDULE SPL TOKEN!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+
+        if *sender_account.key != escrow.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        escrow.schedule.update(
+            &input,
+            escrow.amount,
+            escrow.start_time,
+            escrow.withdrawn,
+            now,
+        )?;
+        msg!("UPDATE SCHEDULE: escrow {:?} schedule {:?}", escrow_account.key, escrow.schedule);
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_create_split(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: SplitInput,
+    ) -> ProgramResult {
+        msg!("INTO CREATE SPLIT NATIVE!");
+        msg!("amount: {:?}", input.amount);
+        msg!("recipients: {:?}", input.recipients.len());
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::load_config(program_id, config_account)?.check_lock_duration(input.lock_duration)?;
+        SplitEscrow::validate_shares(&input.recipients)?;
+
+        let (escrow_address, bump_seed) = find_split_escrow_address(
+            program_id,
+            sender_account.key,
+            input.nonce,
+        );
+        if escrow_address != *escrow_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, escrow_account, vault)?;
+
+        let escrow = SplitEscrow {
+            is_initialized: true,
+            start_time: input.start_time,
+            lock_duration: input.lock_duration,
+            amount: input.amount,
+            sender: *sender_account.key,
+            nonce: input.nonce,
+            recipients: input.recipients
+                .iter()
+                .map(|share| SplitRecipient {
+                    receiver: share.receiver,
+                    share_bps: share.share_bps,
+                    claimed: false,
+                })
+                .collect(),
+        };
+        let escrow_len = escrow.try_to_vec()?.len();
+
+        Self::create_pda_account(
+            program_id,
+            sender_account,
+            escrow_account,
+            system_program,
+            escrow_len,
+            &[
+                PREFIX_SPLIT.as_bytes(),
+                sender_account.key.as_ref(),
+                &input.nonce.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+
+        Self::fund_native_vault(sender_account, vault, system_program, input.amount)?;
+
+        Ok(())
+    }
+
+    fn process_withdraw_split(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS WITHDRAW SPLIT NATIVE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !receiver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = SplitEscrow::try_from_slice(&escrow_account.data.borrow())?;
+
+        let (escrow_address, _bump) = find_split_escrow_address(program_id, &escrow.sender, escrow.nonce);
+        if escrow_address != *escrow_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *sender_account.key != escrow.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow.unlock_time()? > Clock::get()?.unix_timestamp as u64{ // lock duration not passed yet
+            return Err(EscrowError::WithdrawTimeLimitNotExceed.into());
+        }
+
+        let index = escrow.recipients
+            .iter()
+            .position(|recipient| recipient.receiver == *receiver_account.key)
+            .ok_or(EscrowError::RecipientNotFound)?;
+        if escrow.recipients[index].claimed {
+            return Err(EscrowError::AlreadyClaimed.into());
+        }
+        let amount = escrow.share_amount(&escrow.recipients[index]);
+        escrow.recipients[index].claimed = true;
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("paying {:?} lamports to {:?}", amount, receiver_account.key);
+        invoke_signed(
+            &system_instruction::transfer(
+                vault.key,
+                receiver_account.key,
+                amount
+            ),
+            &[
+                vault.clone(),
+                receiver_account.clone(),
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
+        )?;
+
+        if escrow.recipients.iter().all(|recipient| recipient.claimed) {
+            msg!("all shares claimed, closing");
+            Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_create_distributor(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: DistributorInput,
+    ) -> ProgramResult {
+        msg!("INTO CREATE DISTRIBUTOR SPL!");
+        msg!("amount: {:?}", input.amount);
+
+        let account_info_iter = &mut accounts.iter();
+        let distributor_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let sender_associated_info = next_account_info(account_info_iter)?;
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let vault = next_account_info(account_info_iter)?;
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (distributor_address, bump_seed) = find_distributor_address(
+            program_id,
+            sender_account.key,
+            input.nonce,
+        );
+        if distributor_address != *distributor_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, distributor_account, vault)?;
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if input.expires_at <= Clock::get()?.unix_timestamp as u64 {
+            return Err(EscrowError::DistributionExpired.into());
+        }
+
+        let mut distributor = MerkleDistributor {
+            is_initialized: true,
+            sender: *sender_account.key,
+            token_mint: *token_mint_info.key,
+            nonce: input.nonce,
+            merkle_root: input.merkle_root,
+            total_amount: input.amount,
+            claimed_amount: 0,
+            expires_at: input.expires_at,
+        };
+        let distributor_len = distributor.try_to_vec()?.len();
+
+        Self::create_pda_account(
+            program_id,
+            sender_account,
+            distributor_account,
+            system_program,
+            distributor_len,
+            &[
+                PREFIX_DISTRIBUTOR.as_bytes(),
+                sender_account.key.as_ref(),
+                &input.nonce.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        invoke(
+            &token::create_associated_token_account(
+                sender_account.key,
+                vault.key,
+                token_mint_info.key,
+                token_program_info.key,
+            ),
+            &[
+                sender_account.clone(),
+                vault_associated_info.clone(),
+                vault.clone(),
+                token_mint_info.clone(),
+                system_program.clone(),
+                token_program_info.clone(),
+                rent_info.clone(),
+                associated_token_info.clone(),
+            ]
+        )?;
+
+        let decimals = token::unpack_mint(&token_mint_info.data.borrow())?.decimals;
+        Self::transfer_tokens(
+            token_program_info,
+            sender_associated_info,
+            token_mint_info,
+            vault_associated_info,
+            sender_account,
+            input.amount,
+            decimals,
+            &[],
+        )?;
+
+        // fee-bearing mints deliver less than was sent, claims are capped by what actually arrived
+        distributor.total_amount = Self::vault_balance(vault_associated_info)?;
+        msg!("vault received {:?} of {:?}", distributor.total_amount, input.amount);
+
+        distributor.serialize(&mut &mut distributor_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_claim_distribution(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ClaimInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS CLAIM DISTRIBUTION SPL!!");
+        msg!("amount {:?}", input.amount);
+
+        let account_info_iter = &mut accounts.iter();
+        let distributor_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let claim_status_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let receiver_associated_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+
+        if *distributor_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !receiver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut distributor = MerkleDistributor::try_from_slice(&distributor_account.data.borrow())?;
+
+        let (distributor_address, _bump) = find_distributor_address(program_id, &distributor.sender, distributor.nonce);
+        if distributor_address != *distributor_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if distributor.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if distributor.expires_at <= Clock::get()?.unix_timestamp as u64 {
+            return Err(EscrowError::DistributionExpired.into());
+        }
+
+        let (claim_status_address, claim_bump) = find_claim_status_address(
+            program_id,
+            distributor_account.key,
+            receiver_account.key,
+        );
+        if claim_status_address != *claim_status_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        // lamports alone do not mean a claim, anyone can send some to the address
+        if *claim_status_account.owner == *program_id && !claim_status_account.data_is_empty() {
+            return Err(EscrowError::AlreadyClaimed.into());
+        }
+
+        let leaf = distribution_leaf(receiver_account.key, input.amount);
+        if !verify_proof(&input.proof, &distributor.merkle_root, leaf) {
+            return Err(EscrowError::InvalidProof.into());
+        }
+
+        distributor.claimed_amount = distributor.claimed_amount
+            .checked_add(input.amount)
+            .ok_or(EscrowError::Overflow)?;
+        if distributor.claimed_amount > distributor.total_amount {
+            return Err(EscrowError::WithdrawAmountExceeded.into());
+        }
+
+        // recording the claim, paid by the receiver
+        let claim_status = ClaimStatus {
+            receiver: *receiver_account.key,
+            amount: input.amount,
+        };
+        let claim_status_len = claim_status.try_to_vec()?.len();
+        Self::create_pda_account(
+            program_id,
+            receiver_account,
+            claim_status_account,
+            system_program,
+            claim_status_len,
+            &[
+                PREFIX_CLAIM.as_bytes(),
+                distributor_account.key.as_ref(),
+                receiver_account.key.as_ref(),
+                &[claim_bump],
+            ],
+        )?;
+        claim_status.serialize(&mut &mut claim_status_account.data.borrow_mut()[..])?;
+
+        Self::create_receiver_token_account(
+            receiver_account,
+            receiver_associated_info,
+            token_mint_info,
+            system_program,
+            token_program_info,
+            rent_info,
+            associated_token_info,
+        )?;
+
+        let vault_bump = Self::assert_vault_address(program_id, distributor_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &distributor_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        let decimals = token::unpack_mint(&token_mint_info.data.borrow())?.decimals;
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            receiver_associated_info,
+            vault,
+            input.amount,
+            decimals,
+            pda_signer_seeds,
+        )?;
+        msg!("claimed {:?} of {:?}", distributor.claimed_amount, distributor.total_amount);
+
+        distributor.serialize(&mut &mut distributor_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_clawback_distribution(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS CLAWBACK DISTRIBUTION SPL!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let distributor_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let sender_associated_info = next_account_info(account_info_iter)?;
+
+        if *distributor_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let distributor = MerkleDistributor::try_from_slice(&distributor_account.data.borrow())?;
+
+        let (distributor_address, _bump) = find_distributor_address(program_id, &distributor.sender, distributor.nonce);
+        if distributor_address != *distributor_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *sender_account.key != distributor.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if distributor.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if distributor.expires_at > Clock::get()?.unix_timestamp as u64 { // receivers can still claim
+            return Err(EscrowError::DistributionNotExpired.into());
+        }
+
+        let vault_bump = Self::assert_vault_address(program_id, distributor_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &distributor_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("claimed {:?} of {:?}", distributor.claimed_amount, distributor.total_amount);
+        // the unclaimed tokens are all the vault holds, swept to the sender while closing it
+        let decimals = token::unpack_mint(&token_mint_info.data.borrow())?.decimals;
+        Self::close_token_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            sender_associated_info,
+            sender_account,
+            vault,
+            decimals,
+            pda_signer_seeds,
+        )?;
+
+        Self::close_account(distributor_account, sender_account)
+    }
+
+    fn process_raise_dispute(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS RAISE DISPUTE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let party_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !party_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        if *party_account.key != escrow_data.sender && *party_account.key != escrow_data.receiver {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow_data.arbiter.is_none() {
+            return Err(EscrowError::NoArbiter.into());
+        }
+
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        escrow_data.disputed = true;
+        msg!("dispute raised by {:?}", party_account.key);
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_resolve_dispute(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ResolveDisputeInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS RESOLVE DISPUTE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let arbiter_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !arbiter_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        match escrow_data.arbiter {
+            None => return Err(EscrowError::NoArbiter.into()),
+            Some(arbiter) if arbiter != *arbiter_account.key => {
+                return Err(EscrowError::ArbiterMismatch.into())
+            }
+            Some(_) => {}
+        }
+
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if *receiver_account.key != escrow_data.receiver {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        let (destination, amount) = if input.release {
+            let remaining = escrow_data.amount_to_send - escrow_data.withdrawn;
+            msg!("releasing {:?} lamports to receiver", remaining);
+            (receiver_account, remaining)
+        } else {
+            msg!("refunding {:?} lamports to sender", vault.lamports());
+            (sender_account, vault.lamports())
+        };
+
+        invoke_signed(
+            &system_instruction::transfer(
+                vault.key,
+                destination.key,
+                amount
+            ),
+            &[
+                vault.clone(),
+                destination.clone(),
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
+        )?;
+        Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+
+        Self::close_account(escrow_account, sender_account)
+    }
+
+    fn process_raise_dispute_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS RAISE DISPUTE SPL TOKEN!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let party_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !party_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+
+        if *party_account.key != escrow.sender && *party_account.key != escrow.receiver {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if escrow.arbiter.is_none() {
+            return Err(EscrowError::NoArbiter.into());
+        }
+
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        escrow.disputed = true;
+        msg!("dispute raised by {:?}", party_account.key);
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_resolve_dispute_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ResolveDisputeInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS RESOLVE DISPUTE SPL TOKEN!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let arbiter_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let destination_token_info = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !arbiter_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+
+        match escrow.arbiter {
+            None => return Err(EscrowError::NoArbiter.into()),
+            Some(arbiter) if arbiter != *arbiter_account.key => {
+                return Err(EscrowError::ArbiterMismatch.into())
+            }
+            Some(_) => {}
+        }
+
+        if escrow.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+
+        if *sender_account.key != escrow.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        // released to the receiver or refunded to the sender, never to a third party
+        let destination_owner = if input.release { escrow.receiver } else { escrow.sender };
+        let destination = token::unpack_account(&destination_token_info.data.borrow())?;
+        if destination.owner != destination_owner || destination.mint != escrow.token_mint {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        let amount = Self::vault_balance(vault_associated_info)?;
+        msg!("paying {:?} tokens to {:?}", amount, destination_owner);
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            destination_token_info,
+            vault,
+            amount,
+            escrow.decimals,
+            pda_signer_seeds,
+        )?;
+
+        Self::close_token_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            destination_token_info,
+            sender_account,
+            vault,
+            escrow.decimals,
+            pda_signer_seeds,
+        )?;
+        Self::close_account(escrow_account, sender_account)
+    }
+
+    fn process_approve(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS APPROVE NATIVE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let approver_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !approver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        let approvals = escrow_data.approvals.as_mut().ok_or(EscrowError::NotAnApprover)?;
+        approvals.approve(approver_account.key)?;
+        msg!("approved by {:?}, {:?} of {:?}", approver_account.key, approvals.approved.count_ones(), approvals.threshold);
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_approve_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS APPROVE SPL TOKEN!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let approver_account = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !approver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+
+        let approvals = escrow.approvals.as_mut().ok_or(EscrowError::NotAnApprover)?;
+        approvals.approve(approver_account.key)?;
+        msg!("approved by {:?}, {:?} of {:?}", approver_account.key, approvals.approved.count_ones(), approvals.threshold);
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_authorized_release(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ReleaseInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS AUTHORIZED RELEASE!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let instructions_sysvar = next_account_info(account_info_iter)?;
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let mut escrow_data = Escrow::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_escrow_address(program_id, escrow_account, &escrow_data)?;
+
+        if *sender_account.key != escrow_data.sender {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if *receiver_account.key != escrow_data.receiver {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if escrow_data.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let authority = escrow_data.release_authority.ok_or(EscrowError::InvalidReleaseAuthorization)?;
+
+        if input.expiry < Clock::get()?.unix_timestamp as u64 {
+            return Err(EscrowError::AuthorizationExpired.into());
+        }
+
+        // the signature must be verified by the instruction right before this one
+        let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+        let previous_index = current_index.checked_sub(1).ok_or(EscrowError::InvalidReleaseAuthorization)?;
+        let verify_instruction = load_instruction_at_checked(previous_index, instructions_sysvar)?;
+        assert_release_authorized(&verify_instruction, &authority, escrow_account.key, input.amount, input.expiry)?;
+
+        if input.amount > escrow_data.amount_to_send {
+            return Err(EscrowError::WithdrawAmountExceeded.into());
+        }
+        let amount = input.amount.saturating_sub(escrow_data.withdrawn);
+        if amount == 0 {
+            return Err(EscrowError::NothingToWithdraw.into());
+        }
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                vault.key,
+                receiver_account.key,
+                amount
+            ),
+            &[
+                vault.clone(),
+                receiver_account.clone(),
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
+        )?;
+
+        escrow_data.withdrawn = input.amount;
+        msg!("withdrawn {:?} of {:?}", escrow_data.withdrawn, escrow_data.amount_to_send);
+
+        if escrow_data.withdrawn == escrow_data.amount_to_send {
+            msg!("escrow fully paid, closing");
+            Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow_data.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_create_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: SwapInput,
+    ) -> ProgramResult {
+        msg!("INTO CREATE SWAP SPL!");
+        msg!("amount a: {:?}, amount b: {:?}", input.amount_a, input.amount_b);
+
+        let account_info_iter = &mut accounts.iter();
+        let swap_account = next_account_info(account_info_iter)?;
+        let initializer_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let mint_a_info = next_account_info(account_info_iter)?;
+        let mint_b_info = next_account_info(account_info_iter)?;
+        let token_program_a_info = next_account_info(account_info_iter)?; // owner of mint A
+        let token_program_b_info = next_account_info(account_info_iter)?; // owner of mint B
+        let initializer_associated_info = next_account_info(account_info_iter)?;
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let vault = next_account_info(account_info_iter)?;
+
+        token::assert_token_program(token_program_a_info, mint_a_info)?;
+        token::assert_token_program(token_program_b_info, mint_b_info)?;
+
+        if !initializer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (swap_address, bump_seed) = find_swap_address(
+            program_id,
+            initializer_account.key,
+            input.nonce,
+        );
+        if swap_address != *swap_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, swap_account, vault)?;
+        Self::assert_vault_token_address(vault, mint_a_info, vault_associated_info)?;
+
+        // the initializer asks for exactly `amount_b`, a fee on token B would shortchange them
+        let fee_b = token::transfer_fee(&mint_b_info.data.borrow(), Clock::get()?.epoch, input.amount_b)?;
+        if fee_b > 0 {
+            msg!("mint B charges a {:?} transfer fee", fee_b);
+            return Err(EscrowError::TransferFeeNotSupported.into());
+        }
+
+        let mut swap = SwapEscrow {
+            is_initialized: true,
+            initializer: *initializer_account.key,
+            mint_a: *mint_a_info.key,
+            mint_b: *mint_b_info.key,
+            amount_a: input.amount_a,
+            amount_b: input.amount_b,
+            nonce: input.nonce,
+        };
+        let swap_len = swap.try_to_vec()?.len();
+
+        Self::create_pda_account(
+            program_id,
+            initializer_account,
+            swap_account,
+            system_program,
+            swap_len,
+            &[
+                PREFIX_SWAP.as_bytes(),
+                initializer_account.key.as_ref(),
+                &input.nonce.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        invoke(
+            &token::create_associated_token_account(
+                initializer_account.key,
+                vault.key,
+                mint_a_info.key,
+                token_program_a_info.key,
+            ),
+            &[
+                initializer_account.clone(),
+                vault_associated_info.clone(),
+                vault.clone(),
+                mint_a_info.clone(),
+                system_program.clone(),
+                token_program_a_info.clone(),
+                rent_info.clone(),
+                associated_token_info.clone(),
+            ]
+        )?;
+
+        let decimals = token::unpack_mint(&mint_a_info.data.borrow())?.decimals;
+        Self::transfer_tokens(
+            token_program_a_info,
+            initializer_associated_info,
+            mint_a_info,
+            vault_associated_info,
+            initializer_account,
+            input.amount_a,
+            decimals,
+            &[],
+        )?;
+
+        // fee-bearing mints deliver less than was sent, the taker gets what actually arrived
+        swap.amount_a = Self::vault_balance(vault_associated_info)?;
+        msg!("vault received {:?} of token A", swap.amount_a);
+
+        swap.serialize(&mut &mut swap_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_take_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS TAKE SWAP SPL!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let swap_account = next_account_info(account_info_iter)?;
+        let taker_account = next_account_info(account_info_iter)?;
+        let initializer_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let mint_a_info = next_account_info(account_info_iter)?;
+        let mint_b_info = next_account_info(account_info_iter)?;
+        let token_program_a_info = next_account_info(account_info_iter)?; // owner of mint A
+        let token_program_b_info = next_account_info(account_info_iter)?; // owner of mint B
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let taker_associated_a_info = next_account_info(account_info_iter)?;
+        let taker_token_b_info = next_account_info(account_info_iter)?;
+        let initializer_associated_b_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+
+        if *swap_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_a_info, mint_a_info)?;
+        token::assert_token_program(token_program_b_info, mint_b_info)?;
+
+        if !taker_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let swap = SwapEscrow::try_from_slice(&swap_account.data.borrow())?;
+
+        let (swap_address, _bump) = find_swap_address(program_id, &swap.initializer, swap.nonce);
+        if swap_address != *swap_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *initializer_account.key != swap.initializer {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if swap.mint_a != *mint_a_info.key || swap.mint_b != *mint_b_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+        Self::assert_vault_token_address(vault, mint_a_info, vault_associated_info)?;
+
+        let initializer_token_b_address = token::get_associated_token_address(
+            initializer_account.key,
+            mint_b_info.key,
+            token_program_b_info.key,
+        );
+        if initializer_token_b_address != *initializer_associated_b_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+
+        // the taker pays for the initializer's token B account when it does not exist yet
+        if initializer_associated_b_info.data_is_empty() {
+            invoke(
+                &token::create_associated_token_account(
+                    taker_account.key,
+                    initializer_account.key,
+                    mint_b_info.key,
+                    token_program_b_info.key,
+                ),
+                &[
+                    taker_account.clone(),
+                    initializer_associated_b_info.clone(),
+                    initializer_account.clone(),
+                    mint_b_info.clone(),
+                    system_program.clone(),
+                    token_program_b_info.clone(),
+                    rent_info.clone(),
+                    associated_token_info.clone(),
+                ]
+            )?;
+        }
+
+        msg!("sending {:?} of token B to initializer", swap.amount_b);
+        let decimals = token::unpack_mint(&mint_b_info.data.borrow())?.decimals;
+        let balance_before = Self::vault_balance(initializer_associated_b_info)?;
+        Self::transfer_tokens(
+            token_program_b_info,
+            taker_token_b_info,
+            mint_b_info,
+            initializer_associated_b_info,
+            taker_account,
+            swap.amount_b,
+            decimals,
+            &[],
+        )?;
+        // a transfer fee enabled on mint B since the swap was created would deliver less
+        let received = Self::vault_balance(initializer_associated_b_info)?
+            .checked_sub(balance_before)
+            .ok_or(EscrowError::Overflow)?;
+        if received != swap.amount_b {
+            msg!("initializer received {:?} of token B", received);
+            return Err(EscrowError::TransferFeeNotSupported.into());
+        }
+
+        Self::create_receiver_token_account(
+            taker_account,
+            taker_associated_a_info,
+            mint_a_info,
+            system_program,
+            token_program_a_info,
+            rent_info,
+            associated_token_info,
+        )?;
+
+        let vault_bump = Self::assert_vault_address(program_id, swap_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &swap_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("sending {:?} of token A to taker", swap.amount_a);
+        let decimals = token::unpack_mint(&mint_a_info.data.borrow())?.decimals;
+        Self::transfer_from_vault(
+            token_program_a_info,
+            vault_associated_info,
+            mint_a_info,
+            taker_associated_a_info,
+            vault,
+            swap.amount_a,
+            decimals,
+            pda_signer_seeds,
+        )?;
+
+        Self::close_token_vault(
+            token_program_a_info,
+            vault_associated_info,
+            mint_a_info,
+            taker_associated_a_info,
+            initializer_account,
+            vault,
+            decimals,
+            pda_signer_seeds,
+        )?;
+        Self::close_account(swap_account, initializer_account)
+    }
+
+    fn process_cancel_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS CANCEL SWAP SPL!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let swap_account = next_account_info(account_info_iter)?;
+        let initializer_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let mint_a_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?;
+        let initializer_token_a_info = next_account_info(account_info_iter)?;
+
+        if *swap_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, mint_a_info)?;
+
+        if !initializer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let swap = SwapEscrow::try_from_slice(&swap_account.data.borrow())?;
+
+        let (swap_address, _bump) = find_swap_address(program_id, &swap.initializer, swap.nonce);
+        if swap_address != *swap_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *initializer_account.key != swap.initializer {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if swap.mint_a != *mint_a_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+        Self::assert_vault_token_address(vault, mint_a_info, vault_associated_info)?;
+        let vault_token_account = token::unpack_account(&vault_associated_info.data.borrow())?;
+
+        let vault_bump = Self::assert_vault_address(program_id, swap_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &swap_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        msg!("refunding {:?} of token A to initializer", vault_token_account.amount);
+        let decimals = token::unpack_mint(&mint_a_info.data.borrow())?.decimals;
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            mint_a_info,
+            initializer_token_a_info,
+            vault,
+            vault_token_account.amount,
+            decimals,
+            pda_signer_seeds,
+        )?;
+
+        Self::close_token_vault(
+            token_program_info,
+            vault_associated_info,
+            mint_a_info,
+            initializer_token_a_info,
+            initializer_account,
+            vault,
+            decimals,
+            pda_signer_seeds,
+        )?;
+        Self::close_account(swap_account, initializer_account)
+    }
+
+    fn process_create_basket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: BasketInput,
+    ) -> ProgramResult {
+        msg!("INTO CREATE BASKET!");
+        msg!("lamports: {:?}, legs: {:?}", input.lamports, input.legs.len());
+
+        let account_info_iter = &mut accounts.iter();
+        let basket_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let vault = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::load_config(program_id, config_account)?.check_lock_duration(input.lock_duration)?;
+        BasketEscrow::validate_legs(input.lamports, &input.legs)?;
+
+        let (basket_address, bump_seed) = find_basket_address(
+            program_id,
+            sender_account.key,
+            receiver_account.key,
+            input.nonce,
+        );
+        if basket_address != *basket_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, basket_account, vault)?;
+
+        let mut basket = BasketEscrow {
+            is_initialized: true,
+            start_time: input.start_time,
+            lock_duration: input.lock_duration,
+            sender: *sender_account.key,
+            receiver: *receiver_account.key,
+            nonce: input.nonce,
+            lamports: input.lamports,
+            lamports_claimed: false,
+            legs: input.legs.iter().map(|leg| BasketLeg {
+                mint: leg.mint,
+                amount: leg.amount,
+                decimals: 0,
+                claimed: false,
+            }).collect(),
+        };
+        let basket_len = basket.try_to_vec()?.len();
+
+        Self::create_pda_account(
+            program_id,
+            sender_account,
+            basket_account,
+            system_program,
+            basket_len,
+            &[
+                PREFIX_BASKET.as_bytes(),
+                sender_account.key.as_ref(),
+                receiver_account.key.as_ref(),
+                &input.nonce.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        if basket.lamports > 0 {
+            Self::fund_native_vault(sender_account, vault, system_program, basket.lamports)?;
+        }
+
+        for leg in basket.legs.iter_mut() {
+            let token_mint_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+            let sender_token_info = next_account_info(account_info_iter)?;
+            let vault_associated_info = next_account_info(account_info_iter)?;
+
+            if leg.mint != *token_mint_info.key {
+                return Err(TokenError::PublicKeyMismatch.into());
+            }
+            token::assert_token_program(token_program_info, token_mint_info)?;
+            Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+            leg.decimals = token::unpack_mint(&token_mint_info.data.borrow())?.decimals;
+
+            invoke(
+                &token::create_associated_token_account(
+                    sender_account.key,
+                    vault.key,
+                    token_mint_info.key,
+                    token_program_info.key,
+                ),
+                &[
+                    sender_account.clone(),
+                    vault_associated_info.clone(),
+                    vault.clone(),
+                    token_mint_info.clone(),
+                    system_program.clone(),
+                    token_program_info.clone(),
+                    rent_info.clone(),
+                    associated_token_info.clone(),
+                ]
+            )?;
+
+            Self::transfer_tokens(
+                token_program_info,
+                sender_token_info,
+                token_mint_info,
+                vault_associated_info,
+                sender_account,
+                leg.amount,
+                leg.decimals,
+                &[],
+            )?;
+
+            // fee-bearing mints deliver less than was sent
+            leg.amount = Self::vault_balance(vault_associated_info)?;
+            msg!("leg {:?}: {:?} tokens", leg.mint, leg.amount);
+        }
+
+        basket.serialize(&mut &mut basket_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_withdraw_basket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: WithdrawBasketInput,
+    ) -> ProgramResult {
+        msg!("INTO PROCESS WITHDRAW BASKET!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let basket_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let vault = next_account_info(account_info_iter)?;
+
+        if *basket_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !receiver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut basket = BasketEscrow::try_from_slice(&basket_account.data.borrow())?;
+
+        let (basket_address, _bump) = find_basket_address(
+            program_id,
+            &basket.sender,
+            &basket.receiver,
+            basket.nonce,
+        );
+        if basket_address != *basket_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *receiver_account.key != basket.receiver || *sender_account.key != basket.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if basket.unlock_time()? > Clock::get()?.unix_timestamp as u64 { // lock duration not passed yet
+            return Err(EscrowError::WithdrawTimeLimitNotExceed.into());
+        }
+
+        let unclaimed_lamports = basket.lamports > 0 && !basket.lamports_claimed;
+        let (released, release_lamports): (Vec<usize>, bool) = match input.leg {
+            Some(BasketLegId::Token(index)) => {
+                let leg = basket.legs.get(index as usize).ok_or(EscrowError::InvalidBasket)?;
+                if leg.claimed {
+                    return Err(EscrowError::AlreadyClaimed.into());
+                }
+                (vec![index as usize], false)
+            }
+            Some(BasketLegId::Lamports) => {
+                if basket.lamports == 0 {
+                    return Err(EscrowError::InvalidBasket.into());
+                }
+                if basket.lamports_claimed {
+                    return Err(EscrowError::AlreadyClaimed.into());
+                }
+                (vec![], true)
+            }
+            None => ((0..basket.legs.len()).filter(|i| !basket.legs[*i].claimed).collect(), unclaimed_lamports),
+        };
+
+        let vault_bump = Self::assert_vault_address(program_id, basket_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &basket_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        for index in released {
+            let leg = &mut basket.legs[index];
+            let token_mint_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+            let vault_associated_info = next_account_info(account_info_iter)?;
+            let receiver_associated_info = next_account_info(account_info_iter)?;
+
+            if leg.mint != *token_mint_info.key {
+                return Err(TokenError::PublicKeyMismatch.into());
+            }
+            token::assert_token_program(token_program_info, token_mint_info)?;
+            Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+            Self::assert_vault_funded(vault_associated_info, leg.amount)?;
+
+            Self::create_receiver_token_account(
+                receiver_account,
+                receiver_associated_info,
+                token_mint_info,
+                system_program,
+                token_program_info,
+                rent_info,
+                associated_token_info,
+            )?;
+
+            Self::transfer_from_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                receiver_associated_info,
+                vault,
+                leg.amount,
+                leg.decimals,
+                pda_signer_seeds,
+            )?;
+            Self::close_token_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                receiver_associated_info,
+                sender_account,
+                vault,
+                leg.decimals,
+                pda_signer_seeds,
+            )?;
+
+            leg.claimed = true;
+            msg!("released leg {:?}: {:?} tokens", leg.mint, leg.amount);
+        }
+
+        if release_lamports {
+            msg!("releasing {:?} lamports", basket.lamports);
+            invoke_signed(
+                &system_instruction::transfer(
+                    vault.key,
+                    receiver_account.key,
+                    basket.lamports
+                ),
+                &[
+                    vault.clone(),
+                    receiver_account.clone(),
+                    system_program.clone()
+                ],
+                &[pda_signer_seeds],
+            )?;
+            basket.lamports_claimed = true;
+        }
+
+        if !basket.is_released() {
+            basket.serialize(&mut &mut basket_account.data.borrow_mut()[..])?;
+            return Ok(());
+        }
+
+        msg!("basket fully released, closing");
+        Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+        Self::close_account(basket_account, sender_account)
+    }
+
+    fn process_cancel_basket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        msg!("INTO PROCESS CANCEL BASKET!!");
+
+        let account_info_iter = &mut accounts.iter();
+        let basket_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+
+        if *basket_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let basket = BasketEscrow::try_from_slice(&basket_account.data.borrow())?;
+
+        let (basket_address, _bump) = find_basket_address(
+            program_id,
+            &basket.sender,
+            &basket.receiver,
+            basket.nonce,
+        );
+        if basket_address != *basket_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+
+        if *sender_account.key != basket.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if basket.unlock_time()? <= Clock::get()?.unix_timestamp as u64 { // receiver can already withdraw
+            return Err(EscrowError::CancelTimeLimitExceeded.into());
+        }
+
+        let vault_bump = Self::assert_vault_address(program_id, basket_account, vault)?;
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &basket_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+
+        for leg in basket.legs.iter().filter(|leg| !leg.claimed) {
+            let token_mint_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+            let vault_associated_info = next_account_info(account_info_iter)?;
+            let sender_token_info = next_account_info(account_info_iter)?;
+
+            if leg.mint != *token_mint_info.key {
+                return Err(TokenError::PublicKeyMismatch.into());
+            }
+            token::assert_token_program(token_program_info, token_mint_info)?;
+            Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+            let refund = Self::vault_balance(vault_associated_info)?;
+            msg!("refunding leg {:?}: {:?} tokens", leg.mint, refund);
+            Self::transfer_from_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                sender_token_info,
+                vault,
+                refund,
+                leg.decimals,
+                pda_signer_seeds,
+            )?;
+            Self::close_token_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                sender_token_info,
+                sender_account,
+                vault,
+                leg.decimals,
+                pda_signer_seeds,
+            )?;
+        }
+
+        // the lamports leg and the vault's rent-exempt reserve
+        Self::close_native_vault(vault, sender_account, system_program, pda_signer_seeds)?;
+        Self::close_account(basket_account, sender_account)
+    }
+
+    fn process_init_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ConfigInput,
+    ) -> ProgramResult {
+        msg!("INTO INIT CONFIG!");
+
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::assert_upgrade_authority(program_id, program_data_info, admin_account)?;
+
+        let (config_address, bump_seed) = find_config_address(program_id);
+        if config_address != *config_account.key {
+            return Err(EscrowError::InvalidConfigAddress.into());
+        }
+
+        if !config_account.data_is_empty() {
+            return Err(EscrowError::ConfigAlreadyInitialized.into());
+        }
+
+        if input.min_lock_duration > input.max_lock_duration {
+            return Err(EscrowError::LockDurationOutOfRange.into());
+        }
+
+        Self::create_pda_account(
+            program_id,
+            admin_account,
+            config_account,
+            system_program,
+            Config::LEN,
+            &[PREFIX_CONFIG.as_bytes(), &[bump_seed]],
+        )?;
+
+        let config = Config {
+            is_initialized: true,
+            admin: *admin_account.key,
+            min_lock_duration: input.min_lock_duration,
+            max_lock_duration: input.max_lock_duration,
+        };
+        config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_update_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: ConfigInput,
+    ) -> ProgramResult {
+        msg!("INTO UPDATE CONFIG!");
+
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin_account = next_account_info(account_info_iter)?;
+
+        let mut config = Self::load_config(program_id, config_account)?;
+
+        if !admin_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if config.admin != *admin_account.key {
+            return Err(EscrowError::AdminMismatch.into());
+        }
+
+        if input.min_lock_duration > input.max_lock_duration {
+            return Err(EscrowError::LockDurationOutOfRange.into());
+        }
+
+        config.min_lock_duration = input.min_lock_duration;
+        config.max_lock_duration = input.max_lock_duration;
+        config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Checks `authority` is the upgrade authority recorded in the program's ProgramData account
+    fn assert_upgrade_authority(
+        program_id: &Pubkey,
+        program_data_info: &AccountInfo,
+        authority: &AccountInfo,
+    ) -> ProgramResult {
+        let (program_data_address, _bump) = Pubkey::find_program_address(
+            &[program_id.as_ref()],
+            &bpf_loader_upgradeable::id(),
+        );
+        if program_data_address != *program_data_info.key
+            || *program_data_info.owner != bpf_loader_upgradeable::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        let state = program_data_info
+            .data
+            .borrow()
+            .get(..metadata_len)
+            .and_then(|data| bincode::deserialize(data).ok());
+        match state {
+            Some(UpgradeableLoaderState::ProgramData { upgrade_authority_address: Some(upgrade_authority), .. })
+                if upgrade_authority == *authority.key => Ok(()),
+            Some(UpgradeableLoaderState::ProgramData { .. }) => Err(EscrowError::AdminMismatch.into()),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Creates a PDA owned by this program with transfer, allocate and assign rather than
+    /// `create_account`, which fails once anyone sent lamports to the address
+    fn create_pda_account<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        space: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let required_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(account.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, required_lamports),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space as u64),
+            &[account.clone(), system_program.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, program_id),
+            &[account.clone(), system_program.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Reads the config account after checking it is the program's config PDA
+    fn load_config(
+        program_id: &Pubkey,
+        config_account: &AccountInfo,
+    ) -> Result<Config, ProgramError> {
+        let (config_address, _bump) = find_config_address(program_id);
+        if config_address != *config_account.key {
+            return Err(EscrowError::InvalidConfigAddress.into());
+        }
+
+        if *config_account.owner != *program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        let config = Config::try_from_slice(&config_account.data.borrow())?;
+        if !config.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(config)
+    }
+
+    /// Checks a native escrow account against the PDA derived from its own data
+    fn assert_escrow_address(
+        program_id: &Pubkey,
+        escrow_account: &AccountInfo,
+        escrow: &Escrow,
+    ) -> ProgramResult {
+        let (escrow_address, _bump) = find_escrow_address(
+            program_id,
+            &escrow.sender,
+            &escrow.receiver,
+            escrow.nonce,
+        );
+        if escrow_address != *escrow_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Ok(())
+    }
+
+    /// Checks a token escrow account against the PDA derived from its own data
+    fn assert_token_escrow_address(
+        program_id: &Pubkey,
+        escrow_account: &AccountInfo,
+        escrow: &TransferToken,
+    ) -> ProgramResult {
+        let (escrow_address, _bump) = find_token_escrow_address(
+            program_id,
+            &escrow.sender,
+            &escrow.receiver,
+            escrow.nonce,
+        );
+        if escrow_address != *escrow_account.key {
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Ok(())
+    }
+
+    /// Checks the vault against its PDA and returns the bump needed to sign for it
+    fn assert_vault_address(
+        program_id: &Pubkey,
+        escrow_account: &AccountInfo,
+        vault: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (vault_address, vault_bump) = find_vault_address(program_id, escrow_account.key);
+        if vault_address != *vault.key {
+            return Err(EscrowError::InvalidVaultAddress.into());
+        }
+        Ok(vault_bump)
+    }
+
+    /// Checks the vault token account is the associated token account of the vault
+    fn assert_vault_token_address(
+        vault: &AccountInfo,
+        token_mint_info: &AccountInfo,
+        vault_associated_info: &AccountInfo,
+    ) -> ProgramResult {
+        let vault_token_address = token::get_associated_token_address(
+            vault.key,
+            token_mint_info.key,
+            token_mint_info.owner,
+        );
+        if vault_token_address != *vault_associated_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Reads the value of the escrow's oracle account and checks its release condition
+    fn assert_oracle_condition(
+        condition: &OracleCondition,
+        oracle_account: &AccountInfo,
+    ) -> ProgramResult {
+        if *oracle_account.key != condition.oracle {
+            return Err(EscrowError::InvalidOracleAccount.into());
+        }
+        let value = condition.layout.read_value(&oracle_account.data.borrow())?;
+        msg!("oracle value {:?}", value);
+        if !condition.is_met(value) {
+            return Err(EscrowError::OracleConditionNotMet.into());
+        }
+        Ok(())
+    }
+
+    /// Amount paid by a withdraw: what the receiver asked for on cliff escrows,
+    /// everything released so far on vesting, periodic and milestone schedules
+    fn withdraw_amount(
+        schedule: &Schedule,
+        requested: u64,
+        withdrawable: u64,
+    ) -> Result<u64, ProgramError> {
+        let amount = match schedule {
+            Schedule::Cliff => requested,
+            _ => withdrawable,
+        };
+        if amount > withdrawable {
+            return Err(EscrowError::WithdrawAmountExceeded.into());
+        }
+        if amount == 0 {
+            return Err(EscrowError::NothingToWithdraw.into());
+        }
+        Ok(amount)
+    }
+
+    /// Creates the receiver's associated token account, paid by the receiver, unless it exists
+    fn create_receiver_token_account<'a>(
+        receiver_account: &AccountInfo<'a>,
+        receiver_associated_info: &AccountInfo<'a>,
+        token_mint_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        rent_info: &AccountInfo<'a>,
+        associated_token_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if !receiver_associated_info.data_is_empty() {
+            return Ok(());
+        }
+        invoke(
+            &token::create_associated_token_account(
+                receiver_account.key,
+                receiver_account.key,
+                token_mint_info.key,
+                token_program_info.key,
+            ),
+            &[
+                receiver_account.clone(),
+                receiver_associated_info.clone(),
+                receiver_account.clone(),
+                token_mint_info.clone(),
+                system_program.clone(),
+                token_program_info.clone(),
+                rent_info.clone(),
+                associated_token_info.clone(),
+            ]
+        )
+    }
+
+    /// Raw token amount of an instruction, from its `UiAmount` when one was given
+    fn resolve_ui_amount(amount: u64, ui_amount: Option<UiAmount>, decimals: u8) -> Result<u64, ProgramError> {
+        match ui_amount {
+            None => Ok(amount),
+            Some(ui_amount) => {
+                if ui_amount.decimals != decimals {
+                    return Err(EscrowError::DecimalsMismatch.into());
+                }
+                token::ui_amount_to_amount(&ui_amount.amount, decimals)
+            }
+        }
+    }
+
+    fn vault_balance(vault_associated_info: &AccountInfo) -> Result<u64, ProgramError> {
+        Ok(token::unpack_account(&vault_associated_info.data.borrow())?.amount)
+    }
+
+    /// Checks the vault token account still holds the `owed` tokens
+    fn assert_vault_funded(vault_associated_info: &AccountInfo, owed: u64) -> ProgramResult {
+        let balance = Self::vault_balance(vault_associated_info)?;
+        if balance < owed {
+            msg!("vault holds {:?} of {:?}", balance, owed);
+            return Err(EscrowError::VaultUnderfunded.into());
+        }
+        Ok(())
+    }
+
+    /// Moves tokens signed by `authority`, a wallet signing the transaction or a PDA
+    /// when `signer_seeds` are given. The token program rejects the transfer unless
+    /// `decimals` match the mint.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_tokens<'a>(
+        token_program_info: &AccountInfo<'a>,
+        source_info: &AccountInfo<'a>,
+        token_mint_info: &AccountInfo<'a>,
+        destination_info: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        amount: u64,
+        decimals: u8,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        invoke_signed(
+            &token::transfer_checked(
+                token_program_info.key,
+                source_info.key,
+                token_mint_info.key,
+                destination_info.key,
+                authority.key,
+                amount,
+                decimals,
+            )?,
+            &[
+                token_program_info.clone(),
+                source_info.clone(),
+                token_mint_info.clone(),
+                destination_info.clone(),
+                authority.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+
+    /// Moves tokens out of the vault token account, signed by the vault PDA
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_from_vault<'a>(
+        token_program_info: &AccountInfo<'a>,
+        vault_associated_info: &AccountInfo<'a>,
+        token_mint_info: &AccountInfo<'a>,
+        destination_info: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        amount: u64,
+        decimals: u8,
+        pda_signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        Self::transfer_tokens(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            destination_info,
+            vault,
+            amount,
+            decimals,
+            &[pda_signer_seeds],
+        )
+    }
+
+    /// Funds a native vault with `amount` plus whatever it lacks of the rent-exempt minimum.
+    /// The vault is a data-less system account, the reserve keeps it rent exempt while
+    /// partial withdrawals leave less than that in it and is paid back on close.
+    fn fund_native_vault<'a>(
+        sender_account: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let reserve = Rent::get()?.minimum_balance(0).saturating_sub(vault.lamports());
+        invoke(
+            &system_instruction::transfer(
+                sender_account.key,
+                vault.key,
+                amount.checked_add(reserve).ok_or(EscrowError::Overflow)?
+            ),
+            &[
+                sender_account.clone(),
+                vault.clone(),
+                system_program.clone()
+            ],
+        )
+    }
+
+    /// Drains whatever is left in a native vault, the rent-exempt reserve, back to the sender
+    fn close_native_vault<'a>(
+        vault: &AccountInfo<'a>,
+        sender_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        pda_signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if vault.lamports() == 0 {
+            return Ok(());
+        }
+
+        msg!("returning {:?} vault lamports to sender", vault.lamports());
+        invoke_signed(
+            &system_instruction::transfer(
+                vault.key,
+                sender_account.key,
+                vault.lamports()
+            ),
+            &[
+                vault.clone(),
+                sender_account.clone(),
+                system_program.clone()
+            ],
+            &[pda_signer_seeds],
+        )
+    }
+
+    /// Closes the vault token account, returning its rent to the sender. Tokens still in
+    /// the vault, sent to it on top of the escrowed amount by anyone, go to
+    /// `destination_info` first as the token program only closes empty accounts.
+    /// Transfer fees withheld in a Token-2022 vault are harvested to the mint as well,
+    /// the token program rejects closing an account that still withholds fees.
+    #[allow(clippy::too_many_arguments)]
+    fn close_token_vault<'a>(
+        token_program_info: &AccountInfo<'a>,
+        vault_associated_info: &AccountInfo<'a>,
+        token_mint_info: &AccountInfo<'a>,
+        destination_info: &AccountInfo<'a>,
+        sender_account: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        decimals: u8,
+        pda_signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let surplus = Self::vault_balance(vault_associated_info)?;
+        if surplus > 0 {
+            msg!("sweeping {:?} surplus vault tokens", surplus);
+            Self::transfer_from_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                destination_info,
+                vault,
+                surplus,
+                decimals,
+                pda_signer_seeds,
+            )?;
+        }
+
+        let withheld = token::withheld_amount(&vault_associated_info.data.borrow())?;
+        if withheld > 0 {
+            msg!("harvesting {:?} withheld tokens to the mint", withheld);
+            invoke(
+                &token::harvest_withheld_tokens_to_mint(
+                    token_program_info.key,
+                    token_mint_info.key,
+                    &[vault_associated_info.key],
+                )?,
+                &[
+                    token_program_info.clone(),
+                    token_mint_info.clone(),
+                    vault_associated_info.clone(),
+                ],
+            )?;
+        }
+
+        invoke_signed(
+            &token::close_account(
+                token_program_info.key,
+                vault_associated_info.key,
+                sender_account.key,
+                vault.key,
+            )?,
+            &[
+                token_program_info.clone(),
+                vault_associated_info.clone(),
+                sender_account.clone(),
+                vault.clone(),
+            ],&[pda_signer_seeds],
+        )
+    }
+
+    /// Drains a program owned account into `destination` and wipes its data
+    fn close_account(
+        account: &AccountInfo,
+        destination: &AccountInfo,
+    ) -> ProgramResult {
+        let destination_starting_lamports = destination.lamports();
+        **destination.lamports.borrow_mut() = destination_starting_lamports
+            .checked_add(account.lamports())
+            .ok_or(ProgramError::InvalidArgument)?;
+        **account.lamports.borrow_mut() = 0;
+        account.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    fn process_create_token_transfer(
+        program_id: &Pubkey,
+        accounts: &TokenTransferAccounts,
+        input: InitTokenInput,
+    ) -> ProgramResult{
+        let InitTokenInput { start_time, amount, nonce, lock_duration, schedule, approvals, oracle_condition, ui_amount, arbiter } = input;
+
+        msg!("INTO CREATE TRANSFER SPL!");
+        msg!("start: {:?}", start_time);
+        msg!("amount: {:?}", amount);
+
+        let TokenTransferAccounts {
+            escrow_account,
+            sender_account,
+            receiver_account,
+            system_program,
+            token_mint_info,
+            token_program_info,
+            sender_associated_info,
+            vault_associated_info,
+            rent_info,
+            associated_token_info,
+            vault,
+            config_account,
+        } = *accounts;
+
+        token::assert_token_program(token_program_info, token_mint_info)?;    
+        // Since we are performing system_instruction source account must be signer
+        if !sender_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature); 
+        }
+
+        let decimals = token::unpack_mint(&token_mint_info.data.borrow())?.decimals;
+        let amount = Self::resolve_ui_amount(amount, ui_amount, decimals)?;
+
+        Self::load_config(program_id, config_account)?.check_lock_duration(lock_duration)?;
+        schedule.validate(start_time, amount)?;
+        if let Some(approvals) = &approvals {
+            approvals.validate()?;
+        }
+
+        // periodic and milestone amounts are fixed by the sender and cannot absorb a transfer fee
+        let fee = token::transfer_fee(&token_mint_info.data.borrow(), Clock::get()?.epoch, amount)?;
+        if fee > 0 && matches!(schedule, Schedule::Periodic { .. } | Schedule::Milestones(_)) {
+            msg!("mint charges a {:?} transfer fee", fee);
+            return Err(EscrowError::InvalidSchedule.into());
+        }
+
+        let (escrow_address, bump_seed) = find_token_escrow_address(
+            program_id,
+            sender_account.key,
+            receiver_account.key,
+            nonce,
+        );
+
+        if escrow_address != *escrow_account.key{
+            return Err(EscrowError::InvalidEscrowAddress.into());
+        }
+        Self::assert_vault_address(program_id, escrow_account, vault)?;
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        let mut escrow = TransferToken {
+            start_time,
+            amount,
+            token_mint: *token_mint_info.key,
+            decimals,
+            sender: *sender_account.key,
+            receiver: *receiver_account.key,
+            withdrawn: 0,
+            nonce,
+            lock_duration,
+            schedule,
+            approvals,
+            oracle_condition,
+            arbiter,
+            disputed: false,
+        };
+        let escrow_len = escrow.try_to_vec()?.len();
+
+        Self::create_pda_account(
+            program_id,
+            sender_account,
+            escrow_account,
+            system_program,
+            escrow_len,
+            &[
+                PREFIX_TOKEN.as_bytes(),
+                sender_account.key.as_ref(),
+                receiver_account.key.as_ref(),
+                &nonce.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        //creating associated token program for receiver to transfer token
+        invoke(
+            &token::create_associated_token_account(
+                sender_account.key,
+                vault.key,
+                token_mint_info.key,
+                token_program_info.key,
+            ), 
+            &[
+                sender_account.clone(),
+                vault_associated_info.clone(),
+                vault.clone(),
+                token_mint_info.clone(),
+                system_program.clone(),
+                token_program_info.clone(),
+                rent_info.clone(),
+                associated_token_info.clone(),
+            ]
+        )?;
+
+
+        Self::transfer_tokens(
+            token_program_info,
+            sender_associated_info,
+            token_mint_info,
+            vault_associated_info,
+            sender_account,
+            amount,
+            decimals,
+            &[],
+        )?;
+
+        // fee-bearing mints deliver less than was sent, the escrow holds what actually arrived
+        let received = Self::vault_balance(vault_associated_info)?;
+        if received != amount {
+            msg!("vault received {:?} of {:?}", received, amount);
+            escrow.amount = received;
+        }
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    fn process_transfer_nft(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        input: NftTransferInput,
+    ) -> ProgramResult {
+        msg!("INTO CREATE TRANSFER NFT!");
+
+        // same accounts as a token transfer, the metadata account comes after the config
+        let account_info_iter = &mut accounts.iter();
+        let transfer_accounts = TokenTransferAccounts::next(account_info_iter)?;
+        let token_mint_info = transfer_accounts.token_mint_info;
+
+        token::assert_token_program(transfer_accounts.token_program_info, token_mint_info)?;
+
+        // without a mint authority nobody can mint a second token later on
+        let mint = token::unpack_mint(&token_mint_info.data.borrow())?;
+        if mint.supply != 1 || mint.decimals != 0 || mint.mint_authority.is_some() {
+            return Err(EscrowError::NotAnNft.into());
+        }
+
+        if input.collection.is_some() || input.creator.is_some() {
+            let metadata_info = next_account_info(account_info_iter)?;
+            Metadata::assert_matches(
+                metadata_info,
+                token_mint_info.key,
+                input.collection.as_ref(),
+                input.creator.as_ref(),
+            )?;
+        }
+
+        Self::process_create_token_transfer(
+            program_id,
+            &transfer_accounts,
+            InitTokenInput {
+                start_time: input.start_time,
+                amount: 1,
+                nonce: input.nonce,
+                lock_duration: input.lock_duration,
+                schedule: Schedule::Cliff,
+                approvals: None,
+                oracle_condition: None,
+                ui_amount: None,
+                arbiter: None,
+            },
+        )
+    }
+
+    fn process_withdraw_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        ui_amount: Option<UiAmount>,
+    ) -> ProgramResult {
+
+        msg!("INTO PROCESS WITHDRAW SPL TOKEN!!");
+        msg!("amount {:?}", amount);
+
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let sender_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let receiver_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?; 
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let vault_associated_info = next_account_info(account_info_iter)?; 
+        let receiver_associated_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?; 
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+
+
+        if *escrow_account.owner != *program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        token::assert_token_program(token_program_info, token_mint_info)?;
+
+        if !receiver_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature); 
+        }
+
+        if escrow_account.data_is_empty(){
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let mut escrow = TransferToken::try_from_slice(&escrow_account.data.borrow())?;
+        Self::assert_token_escrow_address(program_id, escrow_account, &escrow)?;
+        Self::assert_vault_token_address(vault, token_mint_info, vault_associated_info)?;
+
+        if escrow.token_mint != *token_mint_info.key {
+            return Err(TokenError::PublicKeyMismatch.into());
+        }
+
+        if *receiver_account.key != escrow.receiver || *sender_account.key != escrow.sender {
+            return Err(TokenError::EscrowMismatch.into());
+        }
+
+        if escrow.disputed {
+            return Err(EscrowError::EscrowDisputed.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp as u64;
+        if escrow.unlock_time()? > now{ // lock duration not passed yet
+            return Err(EscrowError::WithdrawTimeLimitNotExceed.into());
+        }
+
+        if let Some(approvals) = &escrow.approvals {
+            approvals.assert_approved()?;
+        }
+
+        if let Some(condition) = &escrow.oracle_condition {
+            Self::assert_oracle_condition(condition, next_account_info(account_info_iter)?)?;
+        }
+
+        let amount = Self::resolve_ui_amount(amount, ui_amount, escrow.decimals)?;
+        let amount = Self::withdraw_amount(&escrow.schedule, amount, escrow.withdrawable(now)?)?;
+        Self::assert_vault_funded(vault_associated_info, escrow.amount - escrow.withdrawn)?;
+
+        Self::create_receiver_token_account(
+            receiver_account,
+            receiver_associated_info,
+            token_mint_info,
+            system_program,
+            token_program_info,
+            rent_info,
+            associated_token_info,
+        )?;
+
+        let vault_bump = Self::assert_vault_address(program_id, escrow_account, vault)?;
+
+        let pda_signer_seeds: &[&[_]] = &[
+            PREFIX_VAULT.as_bytes(),
+            &escrow_account.key.to_bytes(),
+            &[vault_bump],
+        ];
+        
+        //transfering token to receiver_associated_info
+        Self::transfer_from_vault(
+            token_program_info,
+            vault_associated_info,
+            token_mint_info,
+            receiver_associated_info,
+            vault,
+            amount,
+            escrow.decimals,
+            pda_signer_seeds,
+        )?;
+
+        escrow.withdrawn += amount;
+        escrow.schedule.claim(escrow.start_time, now);
+        msg!("withdrawn {:?} of {:?}", escrow.withdrawn, escrow.amount);
+
+        if escrow.withdrawn == escrow.amount {
+            msg!("escrow fully paid, closing");
+            Self::close_token_vault(
+                token_program_info,
+                vault_associated_info,
+                token_mint_info,
+                receiver_associated_info,
+                sender_account,
+                vault,
+                escrow.decimals,
+                pda_signer_seeds,
+            )?;
+            return Self::close_account(escrow_account, sender_account);
+        }
+
+        escrow.serialize(&mut &mut escrow_account.data.borrow_mut()[..])?;
         Ok(())
 
     }
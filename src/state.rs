@@ -1,22 +1,333 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 
+use crate::{error::EscrowError, oracle::OracleLayout};
+
 use solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
-    program_pack::{IsInitialized, Pack, Sealed},
+    program_pack::IsInitialized,
 };
 
-use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
-
 #[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug)]
 pub struct TransferInput {
     pub start_time: u64,
     pub amount_to_send: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    pub schedule: Schedule,
+    pub hashlock: Option<[u8; 32]>,
+    pub arbiter: Option<Pubkey>,
+    pub approvals: Option<Approvals>,
+    pub release_authority: Option<Pubkey>,
+    pub oracle_condition: Option<OracleCondition>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug)]
 pub struct WithdrawInput{
     pub amount: u64,
+    pub preimage: Vec<u8>,
+}
+
+/// How the escrowed amount is released to the receiver once the lock duration has passed
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum Schedule {
+    /// Everything is released at `start_time + lock_duration`
+    Cliff,
+    /// Released linearly from `start_time` to `end_time`, nothing before `cliff_time`
+    Linear {
+        cliff_time: u64,
+        end_time: u64,
+    },
+    /// `amount_per_period` is released every `period` seconds after `start_time`,
+    /// `periods` times in total, `claimed_periods` of them were already withdrawn
+    Periodic {
+        period: u64,
+        amount_per_period: u64,
+        periods: u64,
+        claimed_periods: u64,
+    },
+    /// Each milestone releases its amount once its unlock time is reached
+    Milestones(Vec<Milestone>),
+    /// `rate` is streamed every second from `start_time` until the budget runs out,
+    /// `stopped_at` is zero while the sender has not stopped the stream.
+    /// `checkpoint_amount` had accrued at `checkpoint_time` when the rate last changed.
+    Stream {
+        rate: u64,
+        stopped_at: u64,
+        checkpoint_time: u64,
+        checkpoint_amount: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Milestone {
+    pub unlock_time: u64,
+    pub amount: u64,
+    pub claimed: bool,
+}
+
+impl Schedule {
+    pub fn validate(&self, start_time: u64, total: u64) -> Result<(), ProgramError> {
+        match self {
+            Schedule::Cliff => Ok(()),
+            Schedule::Linear { cliff_time, end_time } => {
+                if start_time > *cliff_time || cliff_time > end_time || start_time == *end_time {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                Ok(())
+            }
+            Schedule::Periodic { period, amount_per_period, periods, claimed_periods } => {
+                if *period == 0 || *periods == 0 || *claimed_periods != 0 {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                if amount_per_period.checked_mul(*periods) != Some(total) {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                Ok(())
+            }
+            Schedule::Milestones(milestones) => {
+                if milestones.is_empty() || milestones.iter().any(|milestone| milestone.claimed) {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                let sum = milestones
+                    .iter()
+                    .try_fold(0u64, |sum, milestone| sum.checked_add(milestone.amount));
+                if sum != Some(total) {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                Ok(())
+            }
+            Schedule::Stream { rate, stopped_at, checkpoint_time, checkpoint_amount } => {
+                if *rate == 0 || *stopped_at != 0 || *checkpoint_time != 0 || *checkpoint_amount != 0 {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Spreads a top up of `amount` over the schedule, streams must still be running.
+    /// A stream is checkpointed at `now` first so a budget that already ran out does not
+    /// release the whole top up at once.
+    pub fn top_up(&mut self, amount: u64, total: u64, start_time: u64, now: u64) -> Result<(), ProgramError> {
+        let released = self.released_amount(total, start_time, now)?;
+        match self {
+            Schedule::Cliff | Schedule::Linear { .. } => Ok(()),
+            Schedule::Periodic { amount_per_period, periods, .. } => {
+                if amount % *periods != 0 {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                *amount_per_period = amount_per_period
+                    .checked_add(amount / *periods)
+                    .ok_or(EscrowError::Overflow)?;
+                Ok(())
+            }
+            Schedule::Milestones(milestones) => {
+                // the top up is paid with the last milestone
+                let last = milestones.last_mut().ok_or(EscrowError::InvalidSchedule)?;
+                if last.claimed {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                last.amount = last.amount.checked_add(amount).ok_or(EscrowError::Overflow)?;
+                Ok(())
+            }
+            Schedule::Stream { stopped_at: 0, checkpoint_time, checkpoint_amount, .. } => {
+                *checkpoint_time = now;
+                *checkpoint_amount = released;
+                Ok(())
+            }
+            Schedule::Stream { .. } => Err(EscrowError::StreamStopped.into()),
+        }
+    }
+
+    /// Extends the end of a linear schedule or changes the rate of a running stream,
+    /// what accrued before `now` is kept and never drops below `withdrawn`
+    pub fn update(
+        &mut self,
+        input: &UpdateScheduleInput,
+        total: u64,
+        start_time: u64,
+        withdrawn: u64,
+        now: u64,
+    ) -> Result<(), ProgramError> {
+        let released = self.released_amount(total, start_time, now)?;
+        match self {
+            Schedule::Linear { end_time, .. } => {
+                if input.end_time < *end_time {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                *end_time = input.end_time;
+            }
+            Schedule::Stream { rate, stopped_at: 0, checkpoint_time, checkpoint_amount } => {
+                if input.rate == 0 {
+                    return Err(EscrowError::InvalidSchedule.into());
+                }
+                *checkpoint_time = now;
+                *checkpoint_amount = released;
+                *rate = input.rate;
+            }
+            Schedule::Stream { .. } => return Err(EscrowError::StreamStopped.into()),
+            _ => return Err(EscrowError::InvalidSchedule.into()),
+        }
+        if self.released_amount(total, start_time, now)? < withdrawn {
+            return Err(EscrowError::InvalidSchedule.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless this is a stream the sender has not stopped yet
+    pub fn assert_running_stream(&self) -> Result<(), ProgramError> {
+        match self {
+            Schedule::Stream { stopped_at: 0, .. } => Ok(()),
+            Schedule::Stream { .. } => Err(EscrowError::StreamStopped.into()),
+            _ => Err(EscrowError::NotAStream.into()),
+        }
+    }
+
+    /// Freezes a running stream at `now`, nothing accrues afterwards
+    pub fn stop(&mut self, now: u64) -> Result<(), ProgramError> {
+        self.assert_running_stream()?;
+        if let Schedule::Stream { stopped_at, .. } = self {
+            *stopped_at = now;
+        }
+        Ok(())
+    }
+
+    /// Records the release state after everything released by `now` was withdrawn
+    pub fn claim(&mut self, start_time: u64, now: u64) {
+        match self {
+            Schedule::Periodic { period, periods, claimed_periods, .. } => {
+                *claimed_periods = Self::matured_periods(*period, *periods, start_time, now);
+            }
+            Schedule::Milestones(milestones) => {
+                for milestone in milestones.iter_mut().filter(|milestone| milestone.unlock_time <= now) {
+                    milestone.claimed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn matured_periods(period: u64, periods: u64, start_time: u64, now: u64) -> u64 {
+        (now.saturating_sub(start_time) / period).min(periods)
+    }
+
+    /// Part of `total` released by `now`, not counting what was already withdrawn
+    pub fn released_amount(&self, total: u64, start_time: u64, now: u64) -> Result<u64, ProgramError> {
+        match self {
+            Schedule::Cliff => Ok(total),
+            Schedule::Linear { cliff_time, end_time } => {
+                if now < *cliff_time {
+                    return Ok(0);
+                }
+                if now >= *end_time {
+                    return Ok(total);
+                }
+                let vested = (total as u128)
+                    .checked_mul(now.saturating_sub(start_time) as u128)
+                    .ok_or(EscrowError::Overflow)?
+                    / (end_time - start_time) as u128;
+                Ok(vested as u64)
+            }
+            Schedule::Periodic { period, amount_per_period, periods, .. } => {
+                let matured = Self::matured_periods(*period, *periods, start_time, now);
+                amount_per_period
+                    .checked_mul(matured)
+                    .ok_or_else(|| EscrowError::Overflow.into())
+            }
+            Schedule::Milestones(milestones) => milestones
+                .iter()
+                .filter(|milestone| milestone.unlock_time <= now)
+                .try_fold(0u64, |sum, milestone| sum.checked_add(milestone.amount))
+                .ok_or_else(|| EscrowError::Overflow.into()),
+            Schedule::Stream { rate, stopped_at, checkpoint_time, checkpoint_amount } => {
+                let end = if *stopped_at == 0 { now } else { now.min(*stopped_at) };
+                let from = start_time.max(*checkpoint_time);
+                let streamed = (*checkpoint_amount as u128)
+                    + (*rate as u128) * (end.saturating_sub(from) as u128);
+                Ok(streamed.min(total as u128) as u64)
+            }
+        }
+    }
+}
+
+/// Most approvers an escrow can have, one bit each in `Approvals::approved`
+pub const MAX_APPROVERS: usize = 64;
+
+/// `threshold` of the `approvers` must `Approve` before the receiver can withdraw
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Approvals {
+    pub threshold: u8,
+    pub approvers: Vec<Pubkey>,
+    /// Bit `i` is set once `approvers[i]` approved, must be zero at creation
+    pub approved: u64,
+}
+
+impl Approvals {
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        let count = self.approvers.len();
+        if self.threshold == 0 || self.threshold as usize > count || count > MAX_APPROVERS || self.approved != 0 {
+            return Err(EscrowError::InvalidApprovers.into());
+        }
+        for (i, approver) in self.approvers.iter().enumerate() {
+            if self.approvers[..i].contains(approver) {
+                return Err(EscrowError::InvalidApprovers.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the approval of `approver`, each approver counts once
+    pub fn approve(&mut self, approver: &Pubkey) -> Result<(), ProgramError> {
+        let index = self
+            .approvers
+            .iter()
+            .position(|key| key == approver)
+            .ok_or(EscrowError::NotAnApprover)?;
+        let bit = 1u64 << index;
+        if self.approved & bit != 0 {
+            return Err(EscrowError::AlreadyApproved.into());
+        }
+        self.approved |= bit;
+        Ok(())
+    }
+
+    pub fn assert_approved(&self) -> Result<(), ProgramError> {
+        if self.approved.count_ones() < self.threshold as u32 {
+            return Err(EscrowError::ApprovalsPending.into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum Comparison {
+    GreaterThan,
+    GreaterOrEqual,
+    LessThan,
+    LessOrEqual,
+    Equal,
+}
+
+/// Withdrawals wait until the value of the `oracle` account compares to `threshold`
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct OracleCondition {
+    pub oracle: Pubkey,
+    pub layout: OracleLayout,
+    pub comparison: Comparison,
+    pub threshold: i64,
+}
+
+impl OracleCondition {
+    pub fn is_met(&self, value: i64) -> bool {
+        match self.comparison {
+            Comparison::GreaterThan => value > self.threshold,
+            Comparison::GreaterOrEqual => value >= self.threshold,
+            Comparison::LessThan => value < self.threshold,
+            Comparison::LessOrEqual => value <= self.threshold,
+            Comparison::Equal => value == self.threshold,
+        }
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug)]
@@ -26,9 +337,39 @@ pub struct Escrow {
     pub receiver: Pubkey,
     pub amount_to_send: u64,
     pub sender: Pubkey,
+    pub withdrawn: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    pub schedule: Schedule,
+    /// SHA-256 of the secret the receiver must reveal, `start_time + lock_duration`
+    /// then becomes the deadline after which only the sender can take the funds back
+    pub hashlock: Option<[u8; 32]>,
+    /// Third party allowed to release the escrow to the receiver or refund the sender at any time
+    pub arbiter: Option<Pubkey>,
+    /// Set by `RaiseDispute`, blocks withdraw and cancel until the arbiter resolves the escrow
+    pub disputed: bool,
+    pub approvals: Option<Approvals>,
+    /// Key whose Ed25519 signed `ed25519::release_message` authorizes `AuthorizedRelease`
+    pub release_authority: Option<Pubkey>,
+    pub oracle_condition: Option<OracleCondition>,
 }
 
-impl Sealed for Escrow {}
+impl Escrow {
+    /// Time from which the receiver may withdraw and the sender can no longer cancel
+    pub fn unlock_time(&self) -> Result<u64, ProgramError> {
+        self.start_time
+            .checked_add(self.lock_duration)
+            .ok_or_else(|| EscrowError::Overflow.into())
+    }
+
+    /// Released but not yet withdrawn lamports at `now`
+    pub fn withdrawable(&self, now: u64) -> Result<u64, ProgramError> {
+        self.schedule
+            .released_amount(self.amount_to_send, self.start_time, now)?
+            .checked_sub(self.withdrawn)
+            .ok_or_else(|| EscrowError::WithdrawAmountExceeded.into())
+    }
+}
 
 impl IsInitialized for Escrow {
     fn is_initialized(&self) -> bool {
@@ -40,71 +381,615 @@ impl IsInitialized for Escrow {
 pub struct InitTokenInput {
     pub start_time: u64,
     pub amount: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    pub schedule: Schedule,
+    pub approvals: Option<Approvals>,
+    pub oracle_condition: Option<OracleCondition>,
+    pub ui_amount: Option<UiAmount>,
+    pub arbiter: Option<Pubkey>,
 }
 
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
 pub struct WithdrawTokenInput{
-    pub amount: u64
+    pub amount: u64,
+    pub ui_amount: Option<UiAmount>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct NftTransferInput {
+    pub start_time: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    /// Verified collection the NFT must belong to
+    pub collection: Option<Pubkey>,
+    /// Verified creator the NFT must list
+    pub creator: Option<Pubkey>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BasketLegInput {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BasketInput {
+    pub start_time: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    /// Native leg held by the vault PDA itself, zero for a token only basket
+    pub lamports: u64,
+    pub legs: Vec<BasketLegInput>,
+}
+
+/// A single leg of a basket, the lamports or the token leg at an index of `BasketEscrow::legs`
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum BasketLegId {
+    Lamports,
+    Token(u8),
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct WithdrawBasketInput {
+    /// Leg to release, every unclaimed leg including the lamports when `None`
+    pub leg: Option<BasketLegId>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    /// Tokens the vault actually received for this leg
+    pub amount: u64,
+    pub decimals: u8,
+    pub claimed: bool,
+}
+
+/// Most token legs a basket can have, each leg adds four accounts to `CreateBasket`,
+/// `WithdrawBasket` and `CancelBasket` so larger baskets need an address lookup table and
+/// a raised compute unit limit
+pub const MAX_BASKET_LEGS: usize = 8;
+
+/// Lamports plus several token legs released together to one receiver, each token leg
+/// sits in the vault's associated token account for its mint
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BasketEscrow {
+    pub is_initialized: bool,
+    pub start_time: u64,
+    pub lock_duration: u64,
+    pub sender: Pubkey,
+    pub receiver: Pubkey,
+    pub nonce: u64,
+    /// Native leg held by the vault PDA itself
+    pub lamports: u64,
+    pub lamports_claimed: bool,
+    pub legs: Vec<BasketLeg>,
+}
+
+impl BasketEscrow {
+    /// Time from which the receiver may withdraw the legs
+    pub fn unlock_time(&self) -> Result<u64, ProgramError> {
+        self.start_time
+            .checked_add(self.lock_duration)
+            .ok_or_else(|| EscrowError::Overflow.into())
+    }
+
+    /// A basket needs something to hold and each mint once, legs share the vault
+    pub fn validate_legs(lamports: u64, legs: &[BasketLegInput]) -> Result<(), ProgramError> {
+        if (lamports == 0 && legs.is_empty()) || legs.len() > MAX_BASKET_LEGS {
+            return Err(EscrowError::InvalidBasket.into());
+        }
+        for (i, leg) in legs.iter().enumerate() {
+            if leg.amount == 0 || legs[..i].iter().any(|other| other.mint == leg.mint) {
+                return Err(EscrowError::InvalidBasket.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Everything was released once the lamports and every token leg were claimed
+    pub fn is_released(&self) -> bool {
+        (self.lamports == 0 || self.lamports_claimed) && self.legs.iter().all(|leg| leg.claimed)
+    }
+}
+
+/// Human readable amount such as `"1.5"`, used instead of the raw amount when given.
+/// `decimals` must match the mint so a client confusing units is rejected.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct UiAmount {
+    pub amount: String,
+    pub decimals: u8,
 }
 
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
 pub struct TransferToken {
     pub start_time: u64,
+    /// Tokens the vault actually received, less than requested for mints with a transfer fee
     pub amount: u64,
     pub token_mint: Pubkey,
+    /// Decimals of `token_mint`, every token move is checked against them
+    pub decimals: u8,
     pub sender: Pubkey,
     pub receiver:Pubkey,
+    pub withdrawn: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    pub schedule: Schedule,
+    pub approvals: Option<Approvals>,
+    pub oracle_condition: Option<OracleCondition>,
+    /// Third party allowed to release the escrow to the receiver or refund the sender at any time
+    pub arbiter: Option<Pubkey>,
+    /// Set by `RaiseDisputeToken`, blocks withdraw and cancel until the arbiter resolves the escrow
+    pub disputed: bool,
+}
+
+impl TransferToken {
+    /// Time from which the receiver may withdraw and the sender can no longer cancel
+    pub fn unlock_time(&self) -> Result<u64, ProgramError> {
+        self.start_time
+            .checked_add(self.lock_duration)
+            .ok_or_else(|| EscrowError::Overflow.into())
+    }
+
+    /// Released but not yet withdrawn tokens at `now`
+    pub fn withdrawable(&self, now: u64) -> Result<u64, ProgramError> {
+        self.schedule
+            .released_amount(self.amount, self.start_time, now)?
+            .checked_sub(self.withdrawn)
+            .ok_or_else(|| EscrowError::WithdrawAmountExceeded.into())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ResolveDisputeInput {
+    /// `true` pays the remaining balance to the receiver, `false` refunds the sender
+    pub release: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ReleaseInput {
+    /// Total amount authorized so far, only the part not yet withdrawn is paid
+    pub amount: u64,
+    pub expiry: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct TopUpInput {
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct TopUpTokenInput {
+    pub amount: u64,
+    pub ui_amount: Option<UiAmount>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BatchTransferInput {
+    pub lock_duration: u64,
+    pub entries: Vec<BatchTransferEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct BatchTransferEntry {
+    pub receiver: Pubkey,
+    pub amount: u64,
+    pub start_time: u64,
+    pub nonce: u64,
 }
 
-impl Pack for Escrow {
-    const LEN: usize = 81;
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, Escrow::LEN];
-        let (
-            is_initialized,
-            start_time,
-            receiver,
-            amount_to_send,
-            sender,
-        ) = array_refs![src, 1, 8, 32, 8, 32];
-
-        let is_initialized = match is_initialized {
-            [0] => false,
-            [1] => true,
-            _ => return Err(ProgramError::InvalidAccountData),
-        };
-
-        Ok(Escrow {
-            is_initialized,
-            start_time: u64::from_le_bytes(*start_time),
-            receiver: Pubkey::new_from_array(*receiver),
-            amount_to_send: u64::from_le_bytes(*amount_to_send),
-            sender: Pubkey::new_from_array(*sender),
-        })
-    }
-
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, Escrow::LEN];
-        let (
-            is_initialized_dst,
-            start_time_dst,
-            receiver_dst,
-            amount_to_send_dst,
-            sender_dst,
-        ) = mut_array_refs![dst,1, 8, 32, 8, 32];
-        
-        let Escrow{
-            is_initialized,
-            start_time,
-            receiver,
-            amount_to_send,
-            sender,
-        } = self;
-
-        is_initialized_dst[0] = *is_initialized as u8;
-        *start_time_dst = start_time.to_le_bytes();
-        receiver_dst.copy_from_slice(receiver.as_ref());
-        *amount_to_send_dst = amount_to_send.to_le_bytes();
-        sender_dst.copy_from_slice(sender.as_ref());
-    }
-}
\ No newline at end of file
+/// Shares of a split escrow are expressed in basis points of the deposit
+pub const SPLIT_TOTAL_BPS: u16 = 10_000;
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SplitInput {
+    pub start_time: u64,
+    pub amount: u64,
+    pub nonce: u64,
+    pub lock_duration: u64,
+    pub recipients: Vec<SplitShare>,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SplitShare {
+    pub receiver: Pubkey,
+    pub share_bps: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SplitRecipient {
+    pub receiver: Pubkey,
+    pub share_bps: u16,
+    pub claimed: bool,
+}
+
+/// One native deposit paid out to several receivers, each claiming their share separately
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SplitEscrow {
+    pub is_initialized: bool,
+    pub start_time: u64,
+    pub lock_duration: u64,
+    pub amount: u64,
+    pub sender: Pubkey,
+    pub nonce: u64,
+    pub recipients: Vec<SplitRecipient>,
+}
+
+impl SplitEscrow {
+    /// Time from which the receivers may withdraw their share
+    pub fn unlock_time(&self) -> Result<u64, ProgramError> {
+        self.start_time
+            .checked_add(self.lock_duration)
+            .ok_or_else(|| EscrowError::Overflow.into())
+    }
+
+    /// Shares must be non zero, cover exactly `SPLIT_TOTAL_BPS` and name each receiver once
+    pub fn validate_shares(shares: &[SplitShare]) -> Result<(), ProgramError> {
+        let mut total_bps: u32 = 0;
+        for (i, share) in shares.iter().enumerate() {
+            if share.share_bps == 0 || shares[..i].iter().any(|other| other.receiver == share.receiver) {
+                return Err(EscrowError::InvalidShares.into());
+            }
+            total_bps += share.share_bps as u32;
+        }
+        if total_bps != SPLIT_TOTAL_BPS as u32 {
+            return Err(EscrowError::InvalidShares.into());
+        }
+        Ok(())
+    }
+
+    /// Lamports owed to a recipient, the rounding dust goes back to the sender on close
+    pub fn share_amount(&self, recipient: &SplitRecipient) -> u64 {
+        ((self.amount as u128) * (recipient.share_bps as u128) / (SPLIT_TOTAL_BPS as u128)) as u64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct DistributorInput {
+    pub merkle_root: [u8; 32],
+    pub amount: u64,
+    pub nonce: u64,
+    /// Unix time from which claims are rejected and the sender can claw back the rest
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ClaimInput {
+    pub amount: u64,
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Token airdrop where each receiver claims the amount committed to in `merkle_root`
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MerkleDistributor {
+    pub is_initialized: bool,
+    pub sender: Pubkey,
+    pub token_mint: Pubkey,
+    pub nonce: u64,
+    pub merkle_root: [u8; 32],
+    /// Tokens the vault actually received, less than deposited for mints with a transfer fee
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub expires_at: u64,
+}
+
+/// Exists once a receiver claimed from a distributor, a PDA of `[PREFIX_CLAIM, distributor, receiver]`
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ClaimStatus {
+    pub receiver: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SwapInput {
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub nonce: u64,
+}
+
+/// `amount_a` of `mint_a` held in the vault, given to whoever sends `amount_b` of `mint_b`
+/// to the initializer
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SwapEscrow {
+    pub is_initialized: bool,
+    pub initializer: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Tokens the vault actually received, less than deposited for mints with a transfer fee
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub nonce: u64,
+}
+
+/// `end_time` applies to linear schedules and `rate` to streams
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct UpdateScheduleInput {
+    pub end_time: u64,
+    pub rate: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ConfigInput {
+    pub min_lock_duration: u64,
+    pub max_lock_duration: u64,
+}
+
+/// Program wide settings, a PDA of `[PREFIX_CONFIG]`
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Config {
+    pub is_initialized: bool,
+    pub admin: Pubkey,
+    pub min_lock_duration: u64,
+    pub max_lock_duration: u64,
+}
+
+impl Config {
+    pub const LEN: usize = 49;
+
+    pub fn check_lock_duration(&self, lock_duration: u64) -> Result<(), ProgramError> {
+        if lock_duration < self.min_lock_duration || lock_duration > self.max_lock_duration {
+            return Err(EscrowError::LockDurationOutOfRange.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_validate() {
+        let linear = |cliff_time, end_time| Schedule::Linear { cliff_time, end_time };
+        assert_eq!(linear(150, 200).validate(100, 1_000), Ok(()));
+        assert_eq!(linear(100, 200).validate(100, 1_000), Ok(()));
+        assert_eq!(linear(200, 200).validate(100, 1_000), Ok(()));
+        // cliff before the start, cliff after the end, nothing to vest over
+        assert_eq!(linear(99, 200).validate(100, 1_000), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(linear(201, 200).validate(100, 1_000), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(linear(100, 100).validate(100, 1_000), Err(EscrowError::InvalidSchedule.into()));
+    }
+
+    #[test]
+    fn test_linear_cliff_and_end() {
+        let schedule = Schedule::Linear { cliff_time: 150, end_time: 200 };
+        assert_eq!(schedule.released_amount(1_000, 100, 0), Ok(0));
+        assert_eq!(schedule.released_amount(1_000, 100, 149), Ok(0));
+        // what vested since the start is released at once at the cliff
+        assert_eq!(schedule.released_amount(1_000, 100, 150), Ok(500));
+        assert_eq!(schedule.released_amount(1_000, 100, 199), Ok(990));
+        assert_eq!(schedule.released_amount(1_000, 100, 200), Ok(1_000));
+        assert_eq!(schedule.released_amount(1_000, 100, u64::MAX), Ok(1_000));
+    }
+
+    #[test]
+    fn test_linear_large_total() {
+        let schedule = Schedule::Linear { cliff_time: 0, end_time: 2 };
+        assert_eq!(schedule.released_amount(u64::MAX, 0, 1), Ok(u64::MAX / 2));
+    }
+
+    #[test]
+    fn test_basket_validate_legs() {
+        let leg = |amount| BasketLegInput { mint: Pubkey::new_unique(), amount };
+        assert_eq!(BasketEscrow::validate_legs(1, &[]), Ok(()));
+        assert_eq!(BasketEscrow::validate_legs(0, &[leg(1)]), Ok(()));
+        assert_eq!(BasketEscrow::validate_legs(0, &[]), Err(EscrowError::InvalidBasket.into()));
+        assert_eq!(BasketEscrow::validate_legs(0, &[leg(0)]), Err(EscrowError::InvalidBasket.into()));
+
+        let first = leg(1);
+        let duplicate = BasketLegInput { mint: first.mint, amount: 2 };
+        assert_eq!(BasketEscrow::validate_legs(0, &[first, duplicate]), Err(EscrowError::InvalidBasket.into()));
+
+        let legs: Vec<_> = (0..=MAX_BASKET_LEGS).map(|_| leg(1)).collect();
+        assert_eq!(BasketEscrow::validate_legs(0, &legs[..MAX_BASKET_LEGS]), Ok(()));
+        assert_eq!(BasketEscrow::validate_legs(0, &legs), Err(EscrowError::InvalidBasket.into()));
+    }
+
+    fn periodic(amount_per_period: u64, periods: u64) -> Schedule {
+        Schedule::Periodic { period: 10, amount_per_period, periods, claimed_periods: 0 }
+    }
+
+    #[test]
+    fn test_periodic_validate() {
+        assert_eq!(periodic(25, 4).validate(100, 100), Ok(()));
+        assert_eq!(periodic(25, 4).validate(100, 99), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(periodic(25, 0).validate(100, 0), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(periodic(u64::MAX, 2).validate(100, u64::MAX), Err(EscrowError::InvalidSchedule.into()));
+        let no_period = Schedule::Periodic { period: 0, amount_per_period: 25, periods: 4, claimed_periods: 0 };
+        assert_eq!(no_period.validate(100, 100), Err(EscrowError::InvalidSchedule.into()));
+        let claimed = Schedule::Periodic { period: 10, amount_per_period: 25, periods: 4, claimed_periods: 1 };
+        assert_eq!(claimed.validate(100, 100), Err(EscrowError::InvalidSchedule.into()));
+    }
+
+    #[test]
+    fn test_periodic_tranches_mature() {
+        let schedule = periodic(25, 4);
+        assert_eq!(schedule.released_amount(100, 100, 50), Ok(0));
+        assert_eq!(schedule.released_amount(100, 100, 109), Ok(0));
+        assert_eq!(schedule.released_amount(100, 100, 110), Ok(25));
+        assert_eq!(schedule.released_amount(100, 100, 139), Ok(75));
+        assert_eq!(schedule.released_amount(100, 100, 140), Ok(100));
+        // no more than `periods` tranches ever mature
+        assert_eq!(schedule.released_amount(100, 100, u64::MAX), Ok(100));
+    }
+
+    #[test]
+    fn test_periodic_claim_caps_at_periods() {
+        let mut schedule = periodic(25, 4);
+        schedule.claim(100, 125);
+        assert_eq!(schedule, Schedule::Periodic { period: 10, amount_per_period: 25, periods: 4, claimed_periods: 2 });
+        schedule.claim(100, 10_000);
+        assert_eq!(schedule, Schedule::Periodic { period: 10, amount_per_period: 25, periods: 4, claimed_periods: 4 });
+    }
+
+    #[test]
+    fn test_periodic_top_up() {
+        let mut schedule = periodic(25, 4);
+        assert_eq!(schedule.top_up(10, 100, 100, 100), Err(EscrowError::InvalidSchedule.into()));
+        schedule.top_up(40, 100, 100, 100).unwrap();
+        assert_eq!(schedule.released_amount(140, 100, 140), Ok(140));
+    }
+
+    fn milestones(milestones: &[(u64, u64)]) -> Schedule {
+        Schedule::Milestones(
+            milestones
+                .iter()
+                .map(|&(unlock_time, amount)| Milestone { unlock_time, amount, claimed: false })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_milestones_validate_sum() {
+        assert_eq!(milestones(&[(10, 30), (20, 70)]).validate(0, 100), Ok(()));
+        assert_eq!(milestones(&[(10, 30), (20, 70)]).validate(0, 99), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(milestones(&[]).validate(0, 0), Err(EscrowError::InvalidSchedule.into()));
+        assert_eq!(
+            milestones(&[(10, u64::MAX), (20, 1)]).validate(0, u64::MAX),
+            Err(EscrowError::InvalidSchedule.into())
+        );
+
+        let mut claimed = milestones(&[(10, 30), (20, 70)]);
+        claimed.claim(0, 10);
+        assert_eq!(claimed.validate(0, 100), Err(EscrowError::InvalidSchedule.into()));
+    }
+
+    #[test]
+    fn test_milestones_release_and_claim() {
+        let mut schedule = milestones(&[(10, 30), (20, 70)]);
+        assert_eq!(schedule.released_amount(100, 0, 9), Ok(0));
+        assert_eq!(schedule.released_amount(100, 0, 10), Ok(30));
+        assert_eq!(schedule.released_amount(100, 0, 20), Ok(100));
+
+        schedule.claim(0, 15);
+        assert_eq!(
+            schedule,
+            Schedule::Milestones(vec![
+                Milestone { unlock_time: 10, amount: 30, claimed: true },
+                Milestone { unlock_time: 20, amount: 70, claimed: false },
+            ])
+        );
+        // claimed milestones still count towards the released amount, `withdrawn` is subtracted
+        assert_eq!(schedule.released_amount(100, 0, 15), Ok(30));
+    }
+
+    #[test]
+    fn test_milestones_top_up_last() {
+        let mut schedule = milestones(&[(10, 30), (20, 70)]);
+        schedule.top_up(50, 100, 0, 0).unwrap();
+        assert_eq!(schedule.released_amount(150, 0, 10), Ok(30));
+        assert_eq!(schedule.released_amount(150, 0, 20), Ok(150));
+
+        schedule.claim(0, 20);
+        assert_eq!(schedule.top_up(50, 150, 0, 20), Err(EscrowError::InvalidSchedule.into()));
+    }
+
+    fn stream(rate: u64) -> Schedule {
+        Schedule::Stream { rate, stopped_at: 0, checkpoint_time: 0, checkpoint_amount: 0 }
+    }
+
+    #[test]
+    fn test_stream_accrues_until_budget_runs_out() {
+        let schedule = stream(3);
+        assert_eq!(schedule.released_amount(100, 10, 5), Ok(0));
+        assert_eq!(schedule.released_amount(100, 10, 10), Ok(0));
+        assert_eq!(schedule.released_amount(100, 10, 20), Ok(30));
+        assert_eq!(schedule.released_amount(100, 10, 50), Ok(100));
+        assert_eq!(schedule.released_amount(u64::MAX, 0, u64::MAX), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_stopped_stream_stops_accruing() {
+        let mut schedule = stream(3);
+        schedule.stop(20).unwrap();
+        assert_eq!(schedule.released_amount(100, 10, 15), Ok(15));
+        assert_eq!(schedule.released_amount(100, 10, 20), Ok(30));
+        assert_eq!(schedule.released_amount(100, 10, 40), Ok(30));
+
+        assert_eq!(schedule.stop(30), Err(EscrowError::StreamStopped.into()));
+        assert_eq!(schedule.assert_running_stream(), Err(EscrowError::StreamStopped.into()));
+        assert_eq!(Schedule::Cliff.stop(30), Err(EscrowError::NotAStream.into()));
+    }
+
+    #[test]
+    fn test_stream_rate_update_checkpoints() {
+        let mut schedule = stream(2);
+        let input = UpdateScheduleInput { end_time: 0, rate: 5 };
+        schedule.update(&input, 1_000, 10, 0, 20).unwrap();
+        assert_eq!(
+            schedule,
+            Schedule::Stream { rate: 5, stopped_at: 0, checkpoint_time: 20, checkpoint_amount: 20 }
+        );
+        assert_eq!(schedule.released_amount(1_000, 10, 20), Ok(20));
+        assert_eq!(schedule.released_amount(1_000, 10, 30), Ok(70));
+
+        let zero_rate = UpdateScheduleInput { end_time: 0, rate: 0 };
+        assert_eq!(schedule.update(&zero_rate, 1_000, 10, 0, 30), Err(EscrowError::InvalidSchedule.into()));
+    }
+
+    #[test]
+    fn test_stream_top_up_after_budget_ran_out() {
+        // rate 1 from t=0 with a budget of 100 ran out at t=100
+        let mut schedule = stream(1);
+        assert_eq!(schedule.released_amount(100, 0, 1_000), Ok(100));
+
+        schedule.top_up(1_000, 100, 0, 1_000).unwrap();
+        assert_eq!(
+            schedule,
+            Schedule::Stream { rate: 1, stopped_at: 0, checkpoint_time: 1_000, checkpoint_amount: 100 }
+        );
+        assert_eq!(schedule.released_amount(1_100, 0, 1_000), Ok(100));
+        assert_eq!(schedule.released_amount(1_100, 0, 1_500), Ok(600));
+        assert_eq!(schedule.released_amount(1_100, 0, 5_000), Ok(1_100));
+    }
+
+    #[test]
+    fn test_stream_top_up_while_running() {
+        let mut schedule = stream(2);
+        schedule.top_up(50, 100, 10, 30).unwrap();
+        assert_eq!(schedule.released_amount(150, 10, 30), Ok(40));
+        assert_eq!(schedule.released_amount(150, 10, 40), Ok(60));
+    }
+
+    #[test]
+    fn test_stopped_stream_rejects_top_up() {
+        let mut schedule = stream(1);
+        schedule.stop(10).unwrap();
+        assert_eq!(schedule.top_up(10, 100, 0, 20), Err(EscrowError::StreamStopped.into()));
+    }
+
+    fn condition(comparison: Comparison, threshold: i64) -> OracleCondition {
+        OracleCondition {
+            oracle: Pubkey::new_unique(),
+            layout: OracleLayout::Test,
+            comparison,
+            threshold,
+        }
+    }
+
+    #[test]
+    fn test_oracle_condition_comparisons() {
+        let cases = [
+            (Comparison::GreaterThan, [false, false, true]),
+            (Comparison::GreaterOrEqual, [false, true, true]),
+            (Comparison::LessThan, [true, false, false]),
+            (Comparison::LessOrEqual, [true, true, false]),
+            (Comparison::Equal, [false, true, false]),
+        ];
+        for (comparison, expected) in cases {
+            let condition = condition(comparison, 100);
+            for (value, met) in [99, 100, 101].into_iter().zip(expected) {
+                assert_eq!(condition.is_met(value), met, "{:?} {}", condition.comparison, value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_oracle_condition_negative_threshold() {
+        let condition = condition(Comparison::LessThan, -5);
+        assert!(condition.is_met(i64::MIN));
+        assert!(condition.is_met(-6));
+        assert!(!condition.is_met(-5));
+        assert!(!condition.is_met(0));
+    }
+}
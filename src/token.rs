@@ -0,0 +1,176 @@
+//! Instructions and account layouts shared by the SPL Token and Token-2022 programs.
+//! Token-2022 keeps the SPL Token instruction encoding and base account layouts,
+//! extensions are appended after them, so both programs are driven through the
+//! `spl-token-2022` builders, which accept either program id.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use crate::error::EscrowError;
+
+use spl_token_2022::{
+    extension::{
+        transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account, Mint},
+};
+
+/// Checks `token_program_info` is SPL Token or Token-2022 and owns the mint
+pub fn assert_token_program(token_program_info: &AccountInfo, token_mint_info: &AccountInfo) -> ProgramResult {
+    spl_token_2022::check_spl_token_program_account(token_program_info.key)?;
+    if token_mint_info.owner != token_program_info.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Associated token account of `wallet` for a mint owned by `token_program_id`
+pub fn get_associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program_id: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address_with_program_id(wallet, mint, token_program_id)
+}
+
+/// Creates the associated token account of `wallet`, paid by `funding`
+pub fn create_associated_token_account(
+    funding: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    spl_associated_token_account::instruction::create_associated_token_account(
+        funding,
+        wallet,
+        mint,
+        token_program_id,
+    )
+}
+
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    spl_token_2022::instruction::transfer_checked(
+        token_program_id,
+        source,
+        mint,
+        destination,
+        authority,
+        &[],
+        amount,
+        decimals,
+    )
+}
+
+pub fn close_account(
+    token_program_id: &Pubkey,
+    account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    spl_token_2022::instruction::close_account(token_program_id, account, destination, owner, &[])
+}
+
+/// Moves the transfer fees withheld in `sources` to the mint, Token-2022 only.
+/// Any account may harvest, no signer is required.
+pub fn harvest_withheld_tokens_to_mint(
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    sources: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+        token_program_id,
+        mint,
+        sources,
+    )
+}
+
+/// Converts a decimal string such as `"1.5"` into base units of a mint with `decimals`,
+/// rejecting more fractional digits than the mint supports
+pub fn ui_amount_to_amount(ui_amount: &str, decimals: u8) -> Result<u64, ProgramError> {
+    let (whole, fraction) = ui_amount.split_once('.').unwrap_or((ui_amount, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > decimals as usize
+        || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    {
+        return Err(EscrowError::InvalidUiAmount.into());
+    }
+    format!("{}{:0<width$}", whole, fraction, width = decimals as usize)
+        .parse::<u64>()
+        .map_err(|_| EscrowError::InvalidUiAmount.into())
+}
+
+/// Reads the base token account layout, ignoring any Token-2022 extensions
+pub fn unpack_account(data: &[u8]) -> Result<Account, ProgramError> {
+    Ok(StateWithExtensions::<Account>::unpack(data)?.base)
+}
+
+/// Reads the base mint layout, ignoring any Token-2022 extensions
+pub fn unpack_mint(data: &[u8]) -> Result<Mint, ProgramError> {
+    Ok(StateWithExtensions::<Mint>::unpack(data)?.base)
+}
+
+/// Fee a Token-2022 mint charges on a transfer of `amount` during `epoch`,
+/// 0 for mints without a transfer fee
+pub fn transfer_fee(mint_data: &[u8], epoch: u64, amount: u64) -> Result<u64, ProgramError> {
+    let mint = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => fee_config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or_else(|| EscrowError::Overflow.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Transfer fees withheld in a token account, always 0 for SPL Token accounts.
+/// Token-2022 refuses to close an account while it withholds fees.
+pub fn withheld_amount(data: &[u8]) -> Result<u64, ProgramError> {
+    let account = StateWithExtensions::<Account>::unpack(data)?;
+    Ok(account
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee_amount| u64::from(fee_amount.withheld_amount)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ui_amount_to_amount() {
+        assert_eq!(ui_amount_to_amount("1.5", 6), Ok(1_500_000));
+        assert_eq!(ui_amount_to_amount("1", 6), Ok(1_000_000));
+        assert_eq!(ui_amount_to_amount("1.", 6), Ok(1_000_000));
+        assert_eq!(ui_amount_to_amount(".5", 6), Ok(500_000));
+        assert_eq!(ui_amount_to_amount("0.000001", 6), Ok(1));
+        assert_eq!(ui_amount_to_amount("42", 0), Ok(42));
+        assert_eq!(ui_amount_to_amount("18446744073709551615", 0), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_ui_amount_to_amount_rejects_invalid() {
+        let invalid = Err(EscrowError::InvalidUiAmount.into());
+        assert_eq!(ui_amount_to_amount("", 6), invalid);
+        assert_eq!(ui_amount_to_amount(".", 6), invalid);
+        // more decimals than the mint has
+        assert_eq!(ui_amount_to_amount("0.0000001", 6), invalid);
+        assert_eq!(ui_amount_to_amount("1.5", 0), invalid);
+        // non-digits, signs and separators
+        assert_eq!(ui_amount_to_amount("1a", 6), invalid);
+        assert_eq!(ui_amount_to_amount("-1", 6), invalid);
+        assert_eq!(ui_amount_to_amount("+1", 6), invalid);
+        assert_eq!(ui_amount_to_amount("1,000", 6), invalid);
+        assert_eq!(ui_amount_to_amount("1.2.3", 6), invalid);
+        assert_eq!(ui_amount_to_amount(" 1", 6), invalid);
+        // u64 overflow once scaled to base units
+        assert_eq!(ui_amount_to_amount("18446744073709551616", 0), invalid);
+        assert_eq!(ui_amount_to_amount("18446744073709.551616", 6), invalid);
+    }
+}
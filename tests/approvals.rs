@@ -0,0 +1,99 @@
+//! Escrows released only once M of their N approvers approved
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    state::{Approvals, Schedule},
+};
+
+const AMOUNT: u64 = 1_000_000;
+
+/// Two of `approvers` must approve
+fn two_of(approvers: &[Keypair]) -> Approvals {
+    Approvals { threshold: 2, approvers: approvers.iter().map(|approver| approver.pubkey()).collect(), approved: 0 }
+}
+
+/// `Approve` for native escrows, `ApproveToken` for token escrows
+fn approve(program_id: Pubkey, tag: u8, escrow: &Pubkey, approver: &Pubkey) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new(*escrow, false), AccountMeta::new_readonly(*approver, true)],
+        data: vec![tag],
+    }
+}
+
+#[tokio::test]
+async fn native_withdraw_needs_the_threshold() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let approvers: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+    let receiver_balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+
+    let mut create = escrow.create(0, 0);
+    (Schedule::Cliff, None::<[u8; 32]>, None::<Pubkey>, Some(two_of(&approvers))).serialize(&mut create.data).unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    let result = process(&mut context, &[escrow.withdraw(AMOUNT)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::ApprovalsPending);
+
+    process(&mut context, &[approve(program_id, 21, &escrow.escrow, &approvers[0].pubkey())], &[&approvers[0]])
+        .await
+        .unwrap();
+    let result = process(&mut context, &[escrow.withdraw(AMOUNT)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::ApprovalsPending);
+
+    // each approver counts once and outsiders not at all
+    let result =
+        process(&mut context, &[approve(program_id, 21, &escrow.escrow, &approvers[0].pubkey())], &[&approvers[0]])
+            .await;
+    assert_escrow_error(result, EscrowError::AlreadyApproved);
+    let result = process(
+        &mut context,
+        &[approve(program_id, 21, &escrow.escrow, &escrow.receiver.pubkey())],
+        &[&escrow.receiver],
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::NotAnApprover);
+
+    process(&mut context, &[approve(program_id, 21, &escrow.escrow, &approvers[2].pubkey())], &[&approvers[2]])
+        .await
+        .unwrap();
+    process(&mut context, &[escrow.withdraw(AMOUNT)], &[&escrow.receiver]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, AMOUNT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn token_withdraw_needs_the_threshold() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let approvers: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+
+    let mut create = escrow.create(0, 0);
+    (Schedule::Cliff, Some(two_of(&approvers))).serialize(&mut create.data).unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    process(&mut context, &[approve(program_id, 22, &escrow.escrow, &approvers[1].pubkey())], &[&approvers[1]])
+        .await
+        .unwrap();
+    let result = process(&mut context, &[escrow.withdraw(DEPOSIT)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::ApprovalsPending);
+
+    process(&mut context, &[approve(program_id, 22, &escrow.escrow, &approvers[0].pubkey())], &[&approvers[0]])
+        .await
+        .unwrap();
+    process(&mut context, &[escrow.withdraw(DEPOSIT)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}
@@ -0,0 +1,84 @@
+//! Native escrows paid on behalf of their release authority, checked by the Ed25519 program
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{
+    ed25519_instruction::new_ed25519_instruction,
+    signature::{Keypair, Signer},
+};
+use transfer_solana_program::{
+    ed25519::release_message,
+    error::EscrowError,
+    state::{Approvals, Schedule},
+};
+
+const AMOUNT: u64 = 1_000_000;
+const EXPIRY: u64 = u64::MAX;
+
+/// The Ed25519 instruction with `authority`'s signature of the release message, then the release
+fn release(escrow: &NativeEscrow, authority: &Keypair, amount: u64) -> [Instruction; 2] {
+    let authority = ed25519_dalek::Keypair::from_bytes(&authority.to_bytes()).unwrap();
+    let verify = new_ed25519_instruction(&authority, &release_message(&escrow.escrow, amount, EXPIRY));
+    [verify, release_without_signature(escrow, amount)]
+}
+
+fn release_without_signature(escrow: &NativeEscrow, amount: u64) -> Instruction {
+    let mut data = vec![23];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&EXPIRY.to_le_bytes());
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new(escrow.sender.pubkey(), false),
+            AccountMeta::new(escrow.receiver.pubkey(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(escrow.vault, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn release_on_behalf_of_the_authority() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let authority = Keypair::new();
+    let now = unix_timestamp(&mut context).await;
+    let receiver_balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+
+    let mut create = escrow.create(now, LONG_LOCK);
+    (Schedule::Cliff, None::<[u8; 32]>, None::<Pubkey>, None::<Approvals>, Some(authority.pubkey()))
+        .serialize(&mut create.data)
+        .unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    let result = process(&mut context, &[release_without_signature(&escrow, AMOUNT)], &[]).await;
+    assert_escrow_error(result, EscrowError::InvalidReleaseAuthorization);
+    let result = process(&mut context, &release(&escrow, &Keypair::new(), AMOUNT), &[]).await;
+    assert_escrow_error(result, EscrowError::InvalidReleaseAuthorization);
+
+    // nobody signs the transaction for the authority, the lock is bypassed
+    process(&mut context, &release(&escrow, &authority, 400_000), &[]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, 400_000);
+
+    // the amount is a running total, replaying the authorization pays nothing more
+    let result = process(&mut context, &release(&escrow, &authority, 400_000), &[]).await;
+    assert_escrow_error(result, EscrowError::NothingToWithdraw);
+
+    process(&mut context, &release(&escrow, &authority, AMOUNT), &[]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, AMOUNT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+}
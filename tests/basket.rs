@@ -0,0 +1,249 @@
+//! Baskets of lamports and token legs, released leg by leg or cancelled
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+};
+use transfer_solana_program::{
+    error::EscrowError,
+    find_basket_address, find_config_address, find_vault_address,
+    state::{BasketInput, BasketLegId, BasketLegInput, WithdrawBasketInput, MAX_BASKET_LEGS},
+    token,
+};
+
+const LAMPORTS: u64 = 100_000;
+
+/// A token leg of `DEPOSIT` tokens
+struct Leg {
+    mint: Pubkey,
+    token_program_id: Pubkey,
+    sender_token: Pubkey,
+    vault_token: Pubkey,
+    receiver_token: Pubkey,
+}
+
+/// A basket of `LAMPORTS` and one SPL Token leg plus one Token-2022 leg charging a 1% fee
+struct Basket {
+    program_id: Pubkey,
+    sender: Keypair,
+    receiver: Keypair,
+    basket: Pubkey,
+    vault: Pubkey,
+    legs: Vec<Leg>,
+}
+
+impl Basket {
+    async fn new(context: &mut ProgramTestContext, program_id: Pubkey) -> Self {
+        let legs = [(spl_token::id(), None), (spl_token_2022::id(), Some((100, u64::MAX)))];
+        Self::with_legs(context, program_id, &legs).await
+    }
+
+    /// A basket of `LAMPORTS` and a leg of a fresh mint per `(token_program_id, transfer_fee)`
+    async fn with_legs(
+        context: &mut ProgramTestContext,
+        program_id: Pubkey,
+        leg_mints: &[(Pubkey, Option<(u16, u64)>)],
+    ) -> Self {
+        let sender = Keypair::new();
+        let receiver = Keypair::new();
+        fund(context, &sender.pubkey(), 1_000_000_000).await;
+        fund(context, &receiver.pubkey(), 1_000_000_000).await;
+
+        let (basket, _) = find_basket_address(&program_id, &sender.pubkey(), &receiver.pubkey(), 0);
+        let (vault, _) = find_vault_address(&program_id, &basket);
+        let mut legs = vec![];
+        for (token_program_id, transfer_fee) in leg_mints.iter().copied() {
+            let mint = create_mint(context, &token_program_id, 6, transfer_fee).await;
+            legs.push(Leg {
+                mint,
+                token_program_id,
+                sender_token: create_token_account(context, &token_program_id, &mint, &sender.pubkey(), DEPOSIT).await,
+                vault_token: token::get_associated_token_address(&vault, &mint, &token_program_id),
+                receiver_token: token::get_associated_token_address(&receiver.pubkey(), &mint, &token_program_id),
+            });
+        }
+        Self { program_id, sender, receiver, basket, vault, legs }
+    }
+
+    fn create(&self, start_time: u64, lock_duration: u64) -> Instruction {
+        let mut data = vec![28];
+        BasketInput {
+            start_time,
+            nonce: 0,
+            lock_duration,
+            lamports: LAMPORTS,
+            legs: self.legs.iter().map(|leg| BasketLegInput { mint: leg.mint, amount: DEPOSIT }).collect(),
+        }
+        .serialize(&mut data)
+        .unwrap();
+        let mut accounts = vec![
+            AccountMeta::new(self.basket, false),
+            AccountMeta::new(self.sender.pubkey(), true),
+            AccountMeta::new_readonly(self.receiver.pubkey(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new(self.vault, false),
+            AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+        ];
+        for leg in &self.legs {
+            accounts.extend([
+                AccountMeta::new_readonly(leg.mint, false),
+                AccountMeta::new_readonly(leg.token_program_id, false),
+                AccountMeta::new(leg.sender_token, false),
+                AccountMeta::new(leg.vault_token, false),
+            ]);
+        }
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    /// Releases `leg`, token legs expect the accounts of every `legs` index
+    fn withdraw(&self, leg: Option<BasketLegId>, legs: &[usize]) -> Instruction {
+        let mut data = vec![29];
+        WithdrawBasketInput { leg }.serialize(&mut data).unwrap();
+        let mut accounts = vec![
+            AccountMeta::new(self.basket, false),
+            AccountMeta::new(self.sender.pubkey(), false),
+            AccountMeta::new(self.receiver.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new(self.vault, false),
+        ];
+        for leg in legs.iter().map(|index| &self.legs[*index]) {
+            accounts.extend([
+                AccountMeta::new(leg.mint, false),
+                AccountMeta::new_readonly(leg.token_program_id, false),
+                AccountMeta::new(leg.vault_token, false),
+                AccountMeta::new(leg.receiver_token, false),
+            ]);
+        }
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    fn cancel(&self) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(self.basket, false),
+            AccountMeta::new(self.sender.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(self.vault, false),
+        ];
+        for leg in &self.legs {
+            accounts.extend([
+                AccountMeta::new(leg.mint, false),
+                AccountMeta::new_readonly(leg.token_program_id, false),
+                AccountMeta::new(leg.vault_token, false),
+                AccountMeta::new(leg.sender_token, false),
+            ]);
+        }
+        Instruction { program_id: self.program_id, accounts, data: vec![30] }
+    }
+}
+
+#[tokio::test]
+async fn withdraw_lamports_then_token_legs() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let basket = Basket::new(&mut context, program_id).await;
+    let sender_balance = get_account(&mut context, &basket.sender.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[basket.create(0, 0)], &[&basket.sender]).await.unwrap();
+
+    let receiver_balance = get_account(&mut context, &basket.receiver.pubkey()).await.unwrap().lamports;
+    let withdraw_lamports = basket.withdraw(Some(BasketLegId::Lamports), &[]);
+    process(&mut context, std::slice::from_ref(&withdraw_lamports), &[&basket.receiver]).await.unwrap();
+    let balance = get_account(&mut context, &basket.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, LAMPORTS);
+    assert!(get_account(&mut context, &basket.basket).await.is_some());
+
+    let result = process(&mut context, &[withdraw_lamports], &[&basket.receiver]).await;
+    assert_escrow_error(result, EscrowError::AlreadyClaimed);
+
+    let result = process(&mut context, &[basket.withdraw(Some(BasketLegId::Token(2)), &[])], &[&basket.receiver]).await;
+    assert_escrow_error(result, EscrowError::InvalidBasket);
+
+    // the remaining token legs go together and close the basket
+    process(&mut context, &[basket.withdraw(None, &[0, 1])], &[&basket.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &basket.legs[0].receiver_token).await, DEPOSIT);
+    assert_eq!(token_balance(&mut context, &basket.legs[1].receiver_token).await, 9_900 - 99);
+    assert!(get_account(&mut context, &basket.basket).await.is_none());
+    assert!(get_account(&mut context, &basket.vault).await.is_none());
+    for leg in &basket.legs {
+        assert!(get_account(&mut context, &leg.vault_token).await.is_none());
+    }
+
+    // the sender paid the lamports and got every rent back
+    let balance = get_account(&mut context, &basket.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(sender_balance - balance, LAMPORTS);
+}
+
+#[tokio::test]
+async fn cancel_refunds_every_leg() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let basket = Basket::new(&mut context, program_id).await;
+    let sender_balance = get_account(&mut context, &basket.sender.pubkey()).await.unwrap().lamports;
+
+    let now = unix_timestamp(&mut context).await;
+    process(&mut context, &[basket.create(now, LONG_LOCK)], &[&basket.sender]).await.unwrap();
+
+    let result = process(&mut context, &[basket.withdraw(None, &[0, 1])], &[&basket.receiver]).await;
+    assert_escrow_error(result, EscrowError::WithdrawTimeLimitNotExceed);
+
+    process(&mut context, &[basket.cancel()], &[&basket.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &basket.legs[0].sender_token).await, DEPOSIT);
+    // 100 withheld by the vault on deposit, 99 on the refund of 9_900
+    assert_eq!(token_balance(&mut context, &basket.legs[1].sender_token).await, 9_900 - 99);
+    assert!(get_account(&mut context, &basket.basket).await.is_none());
+    assert!(get_account(&mut context, &basket.vault).await.is_none());
+    for leg in &basket.legs {
+        assert!(get_account(&mut context, &leg.vault_token).await.is_none());
+    }
+
+    let balance = get_account(&mut context, &basket.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, sender_balance);
+}
+
+#[tokio::test]
+async fn withdraw_at_the_leg_cap_sweeps_every_vault() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let basket = Basket::with_legs(&mut context, program_id, &[(spl_token::id(), None); MAX_BASKET_LEGS]).await;
+
+    // a vault token account per leg takes more than the default compute budget
+    let compute_budget = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+    process(&mut context, &[compute_budget.clone(), basket.create(0, 0)], &[&basket.sender]).await.unwrap();
+
+    // tokens sent to a leg's vault on top of its deposit go to the receiver with the leg
+    mint_to(&mut context, &spl_token::id(), &basket.legs[3].mint, &basket.legs[3].vault_token, 5).await;
+
+    let withdraw = basket.withdraw(None, &(0..MAX_BASKET_LEGS).collect::<Vec<_>>());
+    process(&mut context, &[compute_budget, withdraw], &[&basket.receiver]).await.unwrap();
+    for (index, leg) in basket.legs.iter().enumerate() {
+        let donation = if index == 3 { 5 } else { 0 };
+        assert_eq!(token_balance(&mut context, &leg.receiver_token).await, DEPOSIT + donation);
+        assert!(get_account(&mut context, &leg.vault_token).await.is_none());
+    }
+    assert!(get_account(&mut context, &basket.basket).await.is_none());
+}
+
+#[tokio::test]
+async fn basket_above_the_leg_cap_is_rejected() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let legs = [(spl_token::id(), None); MAX_BASKET_LEGS + 1];
+    let basket = Basket::with_legs(&mut context, program_id, &legs).await;
+
+    let result = process(&mut context, &[basket.create(0, 0)], &[&basket.sender]).await;
+    assert_escrow_error(result, EscrowError::InvalidBasket);
+}
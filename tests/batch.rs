@@ -0,0 +1,99 @@
+//! Several native escrows created in one instruction, all or nothing
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    find_config_address, find_escrow_address, find_vault_address,
+    state::{BatchTransferEntry, BatchTransferInput},
+};
+
+/// `BatchCreateTransfer` of `entries`, each followed by its escrow and vault accounts
+fn batch_create(
+    program_id: Pubkey,
+    sender: &Keypair,
+    entries: Vec<BatchTransferEntry>,
+    escrows: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let mut data = vec![16];
+    BatchTransferInput { lock_duration: 0, entries }.serialize(&mut data).unwrap();
+    let mut accounts = vec![
+        AccountMeta::new(sender.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(&program_id).0, false),
+    ];
+    for (escrow, vault) in escrows {
+        accounts.extend([AccountMeta::new(*escrow, false), AccountMeta::new(*vault, false)]);
+    }
+    Instruction { program_id, accounts, data }
+}
+
+/// One entry per receiver, paying `1_000 * (index + 1)` lamports
+fn entries(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    receivers: &[Pubkey],
+) -> (Vec<BatchTransferEntry>, Vec<(Pubkey, Pubkey)>) {
+    receivers
+        .iter()
+        .enumerate()
+        .map(|(index, receiver)| {
+            let entry =
+                BatchTransferEntry { receiver: *receiver, amount: 1_000 * (index as u64 + 1), start_time: 0, nonce: 0 };
+            let (escrow, _) = find_escrow_address(program_id, sender, receiver, 0);
+            let (vault, _) = find_vault_address(program_id, &escrow);
+            (entry, (escrow, vault))
+        })
+        .unzip()
+}
+
+#[tokio::test]
+async fn batch_creates_every_escrow() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let sender = Keypair::new();
+    fund(&mut context, &sender.pubkey(), 1_000_000_000).await;
+    let receivers: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+    let (entries, escrows) = entries(&program_id, &sender.pubkey(), &receivers);
+    process(&mut context, &[batch_create(program_id, &sender, entries, &escrows)], &[&sender]).await.unwrap();
+
+    let reserve = Rent::default().minimum_balance(0);
+    for (index, (escrow, vault)) in escrows.iter().enumerate() {
+        assert_eq!(get_account(&mut context, escrow).await.unwrap().owner, program_id);
+        let vault = get_account(&mut context, vault).await.unwrap();
+        assert_eq!(vault.lamports, 1_000 * (index as u64 + 1) + reserve);
+    }
+}
+
+#[tokio::test]
+async fn bad_entry_rolls_back_the_batch() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let sender = Keypair::new();
+    fund(&mut context, &sender.pubkey(), 1_000_000_000).await;
+    let sender_balance = get_account(&mut context, &sender.pubkey()).await.unwrap().lamports;
+    let receivers: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+    // the last entry points at the escrow of the first one
+    let (entries, mut escrows) = entries(&program_id, &sender.pubkey(), &receivers);
+    escrows[2] = escrows[0];
+    let result = process(&mut context, &[batch_create(program_id, &sender, entries, &escrows)], &[&sender]).await;
+    assert_escrow_error(result, EscrowError::InvalidEscrowAddress);
+
+    // the escrows created before the bad entry were rolled back with it
+    for (escrow, vault) in &escrows {
+        assert!(get_account(&mut context, escrow).await.is_none());
+        assert!(get_account(&mut context, vault).await.is_none());
+    }
+    assert_eq!(get_account(&mut context, &sender.pubkey()).await.unwrap().lamports, sender_balance);
+}
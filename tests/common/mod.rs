@@ -0,0 +1,405 @@
+//! Program-test setup shared by the integration tests
+
+#![allow(dead_code)]
+
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_2022::{
+    extension::{transfer_fee, ExtensionType},
+    state::Mint,
+};
+use transfer_solana_program::{
+    error::EscrowError, find_config_address, find_escrow_address, find_token_escrow_address, find_vault_address,
+    processor::Processor, state::Config, token,
+};
+
+/// Tokens deposited by `TokenEscrow`
+pub const DEPOSIT: u64 = 10_000;
+
+/// Lock durations far enough in the future that escrows can still be cancelled
+pub const LONG_LOCK: u64 = 1_000_000_000;
+
+/// Program test with only the escrow program, without a config
+pub fn bare_program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "transfer_solana_program",
+        program_id,
+        processor!(Processor::process),
+    );
+    (program_test, program_id)
+}
+
+/// Program test with the escrow program and a config accepting any lock duration
+pub fn program_test() -> (ProgramTest, Pubkey) {
+    let (mut program_test, program_id) = bare_program_test();
+
+    let config = Config {
+        is_initialized: true,
+        admin: Pubkey::new_unique(),
+        min_lock_duration: 0,
+        max_lock_duration: u64::MAX,
+    };
+    program_test.add_account(
+        find_config_address(&program_id).0,
+        Account {
+            lamports: 1_000_000_000,
+            data: config.try_to_vec().unwrap(),
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    (program_test, program_id)
+}
+
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    // a fresh blockhash lets a test resend a transaction that failed before
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Checks the transaction failed in its last instruction with `error`
+pub fn assert_escrow_error(result: Result<(), BanksClientError>, error: EscrowError) {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(ProgramError::Custom(code), error.into())
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+pub async fn fund(context: &mut ProgramTestContext, to: &Pubkey, lamports: u64) {
+    let payer = context.payer.pubkey();
+    process(context, &[system_instruction::transfer(&payer, to, lamports)], &[])
+        .await
+        .unwrap();
+}
+
+/// Creates a mint of `token_program_id`, Token-2022 mints may charge a transfer fee
+/// of `(basis_points, maximum_fee)`
+pub async fn create_mint(
+    context: &mut ProgramTestContext,
+    token_program_id: &Pubkey,
+    decimals: u8,
+    transfer_fee: Option<(u16, u64)>,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let payer = context.payer.pubkey();
+    let extensions: &[ExtensionType] = if transfer_fee.is_some() {
+        &[ExtensionType::TransferFeeConfig]
+    } else {
+        &[]
+    };
+    let space = if *token_program_id == spl_token::id() {
+        Mint::LEN
+    } else {
+        ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()
+    };
+    let rent = context.banks_client.get_rent().await.unwrap();
+
+    let mut instructions = vec![system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        token_program_id,
+    )];
+    if let Some((basis_points, maximum_fee)) = transfer_fee {
+        instructions.push(
+            transfer_fee::instruction::initialize_transfer_fee_config(
+                token_program_id,
+                &mint.pubkey(),
+                Some(&payer),
+                Some(&payer),
+                basis_points,
+                maximum_fee,
+            )
+            .unwrap(),
+        );
+    }
+    instructions.push(
+        spl_token_2022::instruction::initialize_mint(
+            token_program_id,
+            &mint.pubkey(),
+            &payer,
+            None,
+            decimals,
+        )
+        .unwrap(),
+    );
+    process(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+/// Creates the associated token account of `owner` holding `amount` freshly minted tokens
+pub async fn create_token_account(
+    context: &mut ProgramTestContext,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let payer = context.payer.pubkey();
+    let address = token::get_associated_token_address(owner, mint, token_program_id);
+    let mut instructions = vec![token::create_associated_token_account(&payer, owner, mint, token_program_id)];
+    if amount > 0 {
+        instructions.push(
+            spl_token_2022::instruction::mint_to(token_program_id, mint, &address, &payer, &[], amount)
+                .unwrap(),
+        );
+    }
+    process(context, &instructions, &[]).await.unwrap();
+    address
+}
+
+/// Mints `amount` fresh tokens into the token account at `address`, e.g. standing in for
+/// tokens anyone may send to a vault
+pub async fn mint_to(
+    context: &mut ProgramTestContext,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    address: &Pubkey,
+    amount: u64,
+) {
+    let payer = context.payer.pubkey();
+    let instruction =
+        spl_token_2022::instruction::mint_to(token_program_id, mint, address, &payer, &[], amount).unwrap();
+    process(context, &[instruction], &[]).await.unwrap();
+}
+
+pub async fn get_account(context: &mut ProgramTestContext, address: &Pubkey) -> Option<Account> {
+    context.banks_client.get_account(*address).await.unwrap()
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let account = get_account(context, address).await.unwrap();
+    token::unpack_account(&account.data).unwrap().amount
+}
+
+pub async fn unix_timestamp(context: &mut ProgramTestContext) -> u64 {
+    let clock = context
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap();
+    clock.unix_timestamp as u64
+}
+
+/// Moves the clock seen by the program to `unix_timestamp`
+pub async fn set_unix_timestamp(context: &mut ProgramTestContext, unix_timestamp: u64) {
+    let mut clock = context
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap();
+    clock.unix_timestamp = unix_timestamp as i64;
+    context.set_sysvar(&clock);
+}
+
+/// A token escrow of `DEPOSIT` tokens from a fresh sender to a fresh receiver, with
+/// builders for its instructions
+pub struct TokenEscrow {
+    pub program_id: Pubkey,
+    pub token_program_id: Pubkey,
+    pub mint: Pubkey,
+    pub sender: Keypair,
+    pub receiver: Keypair,
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+    pub sender_token: Pubkey,
+    pub vault_token: Pubkey,
+    pub receiver_token: Pubkey,
+}
+
+impl TokenEscrow {
+    pub async fn new(
+        context: &mut ProgramTestContext,
+        program_id: Pubkey,
+        token_program_id: Pubkey,
+        transfer_fee: Option<(u16, u64)>,
+    ) -> Self {
+        let sender = Keypair::new();
+        let receiver = Keypair::new();
+        fund(context, &sender.pubkey(), 1_000_000_000).await;
+        fund(context, &receiver.pubkey(), 1_000_000_000).await;
+
+        let mint = create_mint(context, &token_program_id, 6, transfer_fee).await;
+        let sender_token = create_token_account(context, &token_program_id, &mint, &sender.pubkey(), DEPOSIT).await;
+        let (escrow, _) = find_token_escrow_address(&program_id, &sender.pubkey(), &receiver.pubkey(), 0);
+        let (vault, _) = find_vault_address(&program_id, &escrow);
+        Self {
+            program_id,
+            token_program_id,
+            mint,
+            vault_token: token::get_associated_token_address(&vault, &mint, &token_program_id),
+            receiver_token: token::get_associated_token_address(&receiver.pubkey(), &mint, &token_program_id),
+            sender,
+            receiver,
+            escrow,
+            vault,
+            sender_token,
+        }
+    }
+
+    pub fn create(&self, start_time: u64, lock_duration: u64) -> Instruction {
+        let mut data = vec![2];
+        data.extend_from_slice(&start_time.to_le_bytes());
+        data.extend_from_slice(&DEPOSIT.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&lock_duration.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), true),
+                AccountMeta::new_readonly(self.receiver.pubkey(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program_id, false),
+                AccountMeta::new(self.sender_token, false),
+                AccountMeta::new(self.vault_token, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(self.vault, false),
+                AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+            ],
+            data,
+        }
+    }
+
+    pub fn withdraw(&self, amount: u64) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), false),
+                AccountMeta::new_readonly(self.vault, false),
+                AccountMeta::new(self.receiver.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.mint, false),
+                AccountMeta::new_readonly(self.token_program_id, false),
+                AccountMeta::new(self.vault_token, false),
+                AccountMeta::new(self.receiver_token, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            ],
+            data,
+        }
+    }
+
+    pub fn cancel(&self) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), true),
+                AccountMeta::new_readonly(self.vault, false),
+                AccountMeta::new(self.mint, false),
+                AccountMeta::new_readonly(self.token_program_id, false),
+                AccountMeta::new(self.vault_token, false),
+                AccountMeta::new(self.sender_token, false),
+            ],
+            data: vec![5],
+        }
+    }
+}
+
+/// A native escrow of `amount` lamports from a fresh sender to a fresh receiver, with
+/// builders for its instructions
+pub struct NativeEscrow {
+    pub program_id: Pubkey,
+    pub sender: Keypair,
+    pub receiver: Keypair,
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+}
+
+impl NativeEscrow {
+    pub async fn new(context: &mut ProgramTestContext, program_id: Pubkey, amount: u64) -> Self {
+        let sender = Keypair::new();
+        let receiver = Keypair::new();
+        fund(context, &sender.pubkey(), amount + 1_000_000_000).await;
+        fund(context, &receiver.pubkey(), 1_000_000_000).await;
+
+        let (escrow, _) = find_escrow_address(&program_id, &sender.pubkey(), &receiver.pubkey(), 0);
+        let (vault, _) = find_vault_address(&program_id, &escrow);
+        Self { program_id, sender, receiver, escrow, vault, amount }
+    }
+
+    pub fn create(&self, start_time: u64, lock_duration: u64) -> Instruction {
+        let mut data = vec![0];
+        data.extend_from_slice(&start_time.to_le_bytes());
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&lock_duration.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), true),
+                AccountMeta::new_readonly(self.receiver.pubkey(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+            ],
+            data,
+        }
+    }
+
+    pub fn withdraw(&self, amount: u64) -> Instruction {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), false),
+                AccountMeta::new(self.receiver.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.vault, false),
+            ],
+            data,
+        }
+    }
+
+    pub fn cancel(&self) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.vault, false),
+            ],
+            data: vec![4],
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Config creation restricted to the program's upgrade authority
+
+mod common;
+
+use borsh::BorshDeserialize;
+use common::*;
+use solana_program::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use transfer_solana_program::{error::EscrowError, find_config_address, state::Config};
+
+fn program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+fn init_config(program_id: &Pubkey, admin: &Pubkey, min_lock_duration: u64, max_lock_duration: u64) -> Instruction {
+    let mut data = vec![6];
+    data.extend_from_slice(&min_lock_duration.to_le_bytes());
+    data.extend_from_slice(&max_lock_duration.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(find_config_address(program_id).0, false),
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(program_data_address(program_id), false),
+        ],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn init_config_requires_upgrade_authority() {
+    let (mut program_test, program_id) = bare_program_test();
+    let upgrade_authority = Keypair::new();
+    program_test.add_account(
+        program_data_address(&program_id),
+        Account {
+            lamports: 1_000_000_000,
+            data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: Some(upgrade_authority.pubkey()),
+            })
+            .unwrap(),
+            owner: bpf_loader_upgradeable::id(),
+            ..Account::default()
+        },
+    );
+    // lamports sent to the config address ahead of its creation must not block it
+    let config_address = find_config_address(&program_id).0;
+    program_test.add_account(
+        config_address,
+        Account {
+            lamports: 1_000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+    let mut context = program_test.start_with_context().await;
+    let intruder = Keypair::new();
+    fund(&mut context, &intruder.pubkey(), 1_000_000_000).await;
+    fund(&mut context, &upgrade_authority.pubkey(), 1_000_000_000).await;
+
+    let result = process(&mut context, &[init_config(&program_id, &intruder.pubkey(), 0, 100)], &[&intruder]).await;
+    assert_escrow_error(result, EscrowError::AdminMismatch);
+
+    process(
+        &mut context,
+        &[init_config(&program_id, &upgrade_authority.pubkey(), 10, 100)],
+        &[&upgrade_authority],
+    )
+    .await
+    .unwrap();
+    let account = get_account(&mut context, &config_address).await.unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(
+        Config::try_from_slice(&account.data).unwrap(),
+        Config {
+            is_initialized: true,
+            admin: upgrade_authority.pubkey(),
+            min_lock_duration: 10,
+            max_lock_duration: 100,
+        }
+    );
+
+    let result = process(
+        &mut context,
+        &[init_config(&program_id, &upgrade_authority.pubkey(), 0, 1)],
+        &[&upgrade_authority],
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::ConfigAlreadyInitialized);
+}
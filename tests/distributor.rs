@@ -0,0 +1,189 @@
+//! Merkle distributions, claimed with a proof until they expire and clawed back after
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    find_claim_status_address, find_distributor_address, find_vault_address,
+    merkle::distribution_leaf,
+    state::{ClaimInput, DistributorInput},
+    token,
+};
+
+/// Amounts of the four leaves, one per receiver
+const AMOUNTS: [u64; 4] = [100, 200, 300, 400];
+
+const LIFETIME: u64 = 1_000;
+
+fn parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    if a <= b {
+        keccak::hashv(&[&a, &b]).0
+    } else {
+        keccak::hashv(&[&b, &a]).0
+    }
+}
+
+/// A distributor of `AMOUNTS` to four receivers, funded from the sender's SPL Token account
+struct Distributor {
+    program_id: Pubkey,
+    sender: Keypair,
+    receivers: Vec<Keypair>,
+    mint: Pubkey,
+    sender_token: Pubkey,
+    distributor: Pubkey,
+    vault: Pubkey,
+    vault_token: Pubkey,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl Distributor {
+    async fn new(context: &mut ProgramTestContext, program_id: Pubkey) -> Self {
+        let sender = Keypair::new();
+        fund(context, &sender.pubkey(), 1_000_000_000).await;
+        let receivers: Vec<_> = AMOUNTS.iter().map(|_| Keypair::new()).collect();
+        for receiver in &receivers {
+            fund(context, &receiver.pubkey(), 1_000_000_000).await;
+        }
+
+        let mint = create_mint(context, &spl_token::id(), 6, None).await;
+        let sender_token = create_token_account(context, &spl_token::id(), &mint, &sender.pubkey(), DEPOSIT).await;
+        let (distributor, _) = find_distributor_address(&program_id, &sender.pubkey(), 0);
+        let (vault, _) = find_vault_address(&program_id, &distributor);
+        let leaves = receivers
+            .iter()
+            .zip(AMOUNTS)
+            .map(|(receiver, amount)| distribution_leaf(&receiver.pubkey(), amount))
+            .collect();
+        Self {
+            program_id,
+            sender,
+            receivers,
+            mint,
+            sender_token,
+            distributor,
+            vault,
+            vault_token: token::get_associated_token_address(&vault, &mint, &spl_token::id()),
+            leaves,
+        }
+    }
+
+    fn root(&self) -> [u8; 32] {
+        parent(parent(self.leaves[0], self.leaves[1]), parent(self.leaves[2], self.leaves[3]))
+    }
+
+    /// Sibling hashes from the leaf of receiver `index` up to the root
+    fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        let pair = index / 2 * 2;
+        vec![self.leaves[index ^ 1], parent(self.leaves[2 - pair], self.leaves[3 - pair])]
+    }
+
+    fn receiver_token(&self, index: usize) -> Pubkey {
+        token::get_associated_token_address(&self.receivers[index].pubkey(), &self.mint, &spl_token::id())
+    }
+
+    fn create(&self, expires_at: u64) -> Instruction {
+        let mut data = vec![17];
+        DistributorInput { merkle_root: self.root(), amount: AMOUNTS.iter().sum(), nonce: 0, expires_at }
+            .serialize(&mut data)
+            .unwrap();
+        let accounts = vec![
+            AccountMeta::new(self.distributor, false),
+            AccountMeta::new(self.sender.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.sender_token, false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(self.vault, false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    /// Claims `amount` for receiver `index` with its proof
+    fn claim(&self, index: usize, amount: u64) -> Instruction {
+        let receiver = self.receivers[index].pubkey();
+        let mut data = vec![18];
+        ClaimInput { amount, proof: self.proof(index) }.serialize(&mut data).unwrap();
+        let accounts = vec![
+            AccountMeta::new(self.distributor, false),
+            AccountMeta::new(receiver, true),
+            AccountMeta::new(find_claim_status_address(&self.program_id, &self.distributor, &receiver).0, false),
+            AccountMeta::new_readonly(self.vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new(self.receiver_token(index), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    fn clawback(&self) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(self.distributor, false),
+            AccountMeta::new(self.sender.pubkey(), true),
+            AccountMeta::new_readonly(self.vault, false),
+            AccountMeta::new(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new(self.sender_token, false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data: vec![33] }
+    }
+}
+
+#[tokio::test]
+async fn claim_once_then_clawback_after_expiry() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let distributor = Distributor::new(&mut context, program_id).await;
+    let sender = &distributor.sender;
+    let sender_balance = get_account(&mut context, &sender.pubkey()).await.unwrap().lamports;
+
+    let now = unix_timestamp(&mut context).await;
+    let result = process(&mut context, &[distributor.create(now)], &[sender]).await;
+    assert_escrow_error(result, EscrowError::DistributionExpired);
+    process(&mut context, &[distributor.create(now + LIFETIME)], &[sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &distributor.vault_token).await, 1_000);
+
+    process(&mut context, &[distributor.claim(1, 200)], &[&distributor.receivers[1]]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &distributor.receiver_token(1)).await, 200);
+
+    let result = process(&mut context, &[distributor.claim(1, 200)], &[&distributor.receivers[1]]).await;
+    assert_escrow_error(result, EscrowError::AlreadyClaimed);
+
+    // the proof only holds for the committed amount
+    let result = process(&mut context, &[distributor.claim(2, 400)], &[&distributor.receivers[2]]).await;
+    assert_escrow_error(result, EscrowError::InvalidProof);
+
+    process(&mut context, &[distributor.claim(2, 300)], &[&distributor.receivers[2]]).await.unwrap();
+
+    let result = process(&mut context, &[distributor.clawback()], &[sender]).await;
+    assert_escrow_error(result, EscrowError::DistributionNotExpired);
+
+    set_unix_timestamp(&mut context, now + LIFETIME).await;
+    let result = process(&mut context, &[distributor.claim(0, 100)], &[&distributor.receivers[0]]).await;
+    assert_escrow_error(result, EscrowError::DistributionExpired);
+
+    // the sender gets the unclaimed 500 back along with the rents
+    process(&mut context, &[distributor.clawback()], &[sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &distributor.sender_token).await, DEPOSIT - 500);
+    assert!(get_account(&mut context, &distributor.distributor).await.is_none());
+    assert!(get_account(&mut context, &distributor.vault_token).await.is_none());
+    let balance = get_account(&mut context, &sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, sender_balance);
+}
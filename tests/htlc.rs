@@ -0,0 +1,70 @@
+//! Hashlocked native escrows, paid against the preimage or refunded after the deadline
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{hash::hash, instruction::Instruction};
+use solana_sdk::signature::Signer;
+use transfer_solana_program::{error::EscrowError, state::Schedule};
+
+const AMOUNT: u64 = 1_000_000;
+const DEADLINE: u64 = 1_000;
+const PREIMAGE: &[u8] = b"open sesame";
+
+fn with_hashlock(mut create: Instruction) -> Instruction {
+    Schedule::Cliff.serialize(&mut create.data).unwrap();
+    Some(hash(PREIMAGE).to_bytes()).serialize(&mut create.data).unwrap();
+    create
+}
+
+fn with_preimage(mut withdraw: Instruction, preimage: &[u8]) -> Instruction {
+    withdraw.data.extend_from_slice(preimage);
+    withdraw
+}
+
+#[tokio::test]
+async fn receiver_withdraws_with_the_preimage() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let now = unix_timestamp(&mut context).await;
+    let receiver_balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[with_hashlock(escrow.create(now, DEADLINE))], &[&escrow.sender]).await.unwrap();
+
+    let result =
+        process(&mut context, &[with_preimage(escrow.withdraw(AMOUNT), b"open says me")], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::InvalidPreimage);
+
+    // the receiver may still reveal the preimage
+    let result = process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::RefundTimeoutNotReached);
+
+    process(&mut context, &[with_preimage(escrow.withdraw(AMOUNT), PREIMAGE)], &[&escrow.receiver]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, AMOUNT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+}
+
+#[tokio::test]
+async fn sender_refunds_after_the_deadline() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let now = unix_timestamp(&mut context).await;
+    let sender_balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[with_hashlock(escrow.create(now, DEADLINE))], &[&escrow.sender]).await.unwrap();
+
+    set_unix_timestamp(&mut context, now + DEADLINE).await;
+    let result = process(&mut context, &[with_preimage(escrow.withdraw(AMOUNT), PREIMAGE)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::HashlockExpired);
+
+    process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, sender_balance);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+}
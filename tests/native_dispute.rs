@@ -0,0 +1,133 @@
+//! Native escrows frozen by a dispute and settled by their arbiter
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    state::{ResolveDisputeInput, Schedule, UpdateScheduleInput},
+};
+
+const AMOUNT: u64 = 1_000_000;
+const RATE: u64 = 1_000;
+
+/// Native stream creation with `arbiter` in the trailing optional fields
+fn create_with_arbiter(escrow: &NativeEscrow, start_time: u64, arbiter: &Pubkey) -> Instruction {
+    let mut create = escrow.create(start_time, 0);
+    let stream = Schedule::Stream { rate: RATE, stopped_at: 0, checkpoint_time: 0, checkpoint_amount: 0 };
+    (stream, None::<[u8; 32]>, Some(*arbiter)).serialize(&mut create.data).unwrap();
+    create
+}
+
+fn raise_dispute(escrow: &NativeEscrow, party: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![AccountMeta::new(escrow.escrow, false), AccountMeta::new_readonly(*party, true)],
+        data: vec![19],
+    }
+}
+
+fn resolve_dispute(escrow: &NativeEscrow, arbiter: &Pubkey, release: bool) -> Instruction {
+    let mut data = vec![20];
+    ResolveDisputeInput { release }.serialize(&mut data).unwrap();
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new_readonly(*arbiter, true),
+            AccountMeta::new(escrow.sender.pubkey(), false),
+            AccountMeta::new(escrow.receiver.pubkey(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(escrow.vault, false),
+        ],
+        data,
+    }
+}
+
+fn top_up(escrow: &NativeEscrow, amount: u64) -> Instruction {
+    let mut data = vec![8];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new(escrow.sender.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(escrow.vault, false),
+        ],
+        data,
+    }
+}
+
+fn update_rate(escrow: &NativeEscrow, rate: u64) -> Instruction {
+    let mut data = vec![12];
+    UpdateScheduleInput { end_time: 0, rate }.serialize(&mut data).unwrap();
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![AccountMeta::new(escrow.escrow, false), AccountMeta::new_readonly(escrow.sender.pubkey(), true)],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn dispute_freezes_the_escrow_until_the_arbiter_releases() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let arbiter = Keypair::new();
+    let now = unix_timestamp(&mut context).await;
+    let receiver_balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[create_with_arbiter(&escrow, now, &arbiter.pubkey())], &[&escrow.sender]).await.unwrap();
+
+    process(&mut context, &[raise_dispute(&escrow, &escrow.receiver.pubkey())], &[&escrow.receiver]).await.unwrap();
+    let result = process(&mut context, &[raise_dispute(&escrow, &escrow.sender.pubkey())], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+
+    // neither party can move the funds or change the terms while the dispute is open
+    set_unix_timestamp(&mut context, now + 10).await;
+    let result = process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+    let result = process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+    let result = process(&mut context, &[top_up(&escrow, 1_000)], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+    let result = process(&mut context, &[update_rate(&escrow, 1)], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+
+    let intruder = Keypair::new();
+    let result = process(&mut context, &[resolve_dispute(&escrow, &intruder.pubkey(), true)], &[&intruder]).await;
+    assert_escrow_error(result, EscrowError::ArbiterMismatch);
+
+    process(&mut context, &[resolve_dispute(&escrow, &arbiter.pubkey(), true)], &[&arbiter]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, AMOUNT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+}
+
+#[tokio::test]
+async fn arbiter_refunds_the_sender() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let arbiter = Keypair::new();
+    let now = unix_timestamp(&mut context).await;
+    let sender_balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[create_with_arbiter(&escrow, now, &arbiter.pubkey())], &[&escrow.sender]).await.unwrap();
+    process(&mut context, &[raise_dispute(&escrow, &escrow.sender.pubkey())], &[&escrow.sender]).await.unwrap();
+
+    process(&mut context, &[resolve_dispute(&escrow, &arbiter.pubkey(), false)], &[&arbiter]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, sender_balance);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+}
@@ -0,0 +1,69 @@
+//! Native escrows keep their vault rent exempt until they close
+
+mod common;
+
+use common::*;
+use solana_program::rent::Rent;
+use solana_sdk::signature::Signer;
+
+/// Less than the rent-exempt minimum of the vault on its own
+const AMOUNT: u64 = 100_000;
+
+#[tokio::test]
+async fn partial_withdraw_leaves_vault_rent_exempt() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let sender_balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[escrow.create(0, 0)], &[&escrow.sender]).await.unwrap();
+    let reserve = Rent::default().minimum_balance(0);
+    let vault = get_account(&mut context, &escrow.vault).await.unwrap();
+    assert_eq!(vault.lamports, AMOUNT + reserve);
+
+    // a single lamport is left for the receiver
+    process(&mut context, &[escrow.withdraw(AMOUNT - 1)], &[&escrow.receiver]).await.unwrap();
+    let vault = get_account(&mut context, &escrow.vault).await.unwrap();
+    assert_eq!(vault.lamports, reserve + 1);
+
+    process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.unwrap();
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+
+    // the reserve and the escrow rent are back with the sender
+    let balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(sender_balance - balance, AMOUNT);
+}
+
+#[tokio::test]
+async fn cancel_refunds_the_reserve() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let sender_balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+
+    let now = unix_timestamp(&mut context).await;
+    process(&mut context, &[escrow.create(now, LONG_LOCK)], &[&escrow.sender]).await.unwrap();
+    process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await.unwrap();
+
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+    let balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, sender_balance);
+}
+
+#[tokio::test]
+async fn create_after_lamports_sent_to_the_escrow_address() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+
+    // anyone can fund the predictable escrow address before the sender creates it
+    fund(&mut context, &escrow.escrow, Rent::default().minimum_balance(0)).await;
+    process(&mut context, &[escrow.create(0, 0)], &[&escrow.sender]).await.unwrap();
+    let account = get_account(&mut context, &escrow.escrow).await.unwrap();
+    assert_eq!(account.owner, program_id);
+
+    process(&mut context, &[escrow.withdraw(AMOUNT)], &[&escrow.receiver]).await.unwrap();
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}
@@ -0,0 +1,127 @@
+//! NFTs time-locked to a receiver, optionally checked against their metadata
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use spl_token_2022::instruction::{set_authority, AuthorityType};
+use transfer_solana_program::{
+    error::EscrowError,
+    find_token_escrow_address, find_vault_address,
+    metadata::{find_metadata_address, metadata_program},
+    state::NftTransferInput,
+    token,
+};
+
+/// A token escrow of a fresh zero decimals mint holding a single token, its mint
+/// authority still set
+async fn nft_escrow(context: &mut ProgramTestContext, program_id: Pubkey) -> TokenEscrow {
+    let sender = Keypair::new();
+    let receiver = Keypair::new();
+    fund(context, &sender.pubkey(), 1_000_000_000).await;
+    fund(context, &receiver.pubkey(), 1_000_000_000).await;
+
+    let mint = create_mint(context, &spl_token::id(), 0, None).await;
+    let sender_token = create_token_account(context, &spl_token::id(), &mint, &sender.pubkey(), 1).await;
+    let (escrow, _) = find_token_escrow_address(&program_id, &sender.pubkey(), &receiver.pubkey(), 0);
+    let (vault, _) = find_vault_address(&program_id, &escrow);
+    TokenEscrow {
+        program_id,
+        token_program_id: spl_token::id(),
+        mint,
+        vault_token: token::get_associated_token_address(&vault, &mint, &spl_token::id()),
+        receiver_token: token::get_associated_token_address(&receiver.pubkey(), &mint, &spl_token::id()),
+        sender,
+        receiver,
+        escrow,
+        vault,
+        sender_token,
+    }
+}
+
+async fn remove_mint_authority(context: &mut ProgramTestContext, mint: &Pubkey) {
+    let payer = context.payer.pubkey();
+    let instruction = set_authority(&spl_token::id(), mint, None, AuthorityType::MintTokens, &payer, &[]).unwrap();
+    process(context, &[instruction], &[]).await.unwrap();
+}
+
+/// `TransferNft` takes the accounts of `TransferToken`, then the metadata account when
+/// a creator is expected
+fn transfer_nft(escrow: &TokenEscrow, creator: Option<Pubkey>) -> Instruction {
+    let mut transfer = escrow.create(0, 0);
+    transfer.data = vec![27];
+    NftTransferInput { start_time: 0, nonce: 0, lock_duration: 0, collection: None, creator }
+        .serialize(&mut transfer.data)
+        .unwrap();
+    if creator.is_some() {
+        let metadata = find_metadata_address(&escrow.mint).0;
+        transfer.accounts.push(AccountMeta::new_readonly(metadata, false));
+    }
+    transfer
+}
+
+/// Metadata account of `mint` listing `creator` as its only, verified, creator
+fn set_metadata(context: &mut ProgramTestContext, mint: &Pubkey, creator: &Pubkey) {
+    let mut data = vec![4];
+    data.extend_from_slice(&Pubkey::new_unique().to_bytes());
+    data.extend_from_slice(&mint.to_bytes());
+    for field in ["Escrowed NFT", "ENFT", "https://example.com/nft.json"] {
+        field.to_string().serialize(&mut data).unwrap();
+    }
+    500u16.serialize(&mut data).unwrap();
+    Some(vec![(*creator, true, 100u8)]).serialize(&mut data).unwrap();
+    data.extend_from_slice(&[1, 1, 0, 0, 0]); // primary sale, mutable, no edition nonce, standard or collection
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: metadata_program::id(),
+        ..Account::default()
+    };
+    context.set_account(&find_metadata_address(mint).0, &account.into());
+}
+
+#[tokio::test]
+async fn nft_is_escrowed_and_withdrawn() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = nft_escrow(&mut context, program_id).await;
+
+    // whoever holds the mint authority could still mint a second token
+    let result = process(&mut context, &[transfer_nft(&escrow, None)], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::NotAnNft);
+
+    remove_mint_authority(&mut context, &escrow.mint).await;
+    process(&mut context, &[transfer_nft(&escrow, None)], &[&escrow.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.vault_token).await, 1);
+
+    process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, 1);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
+
+#[tokio::test]
+async fn nft_must_list_the_expected_creator() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = nft_escrow(&mut context, program_id).await;
+    let creator = Pubkey::new_unique();
+    remove_mint_authority(&mut context, &escrow.mint).await;
+    set_metadata(&mut context, &escrow.mint, &creator);
+
+    let result = process(&mut context, &[transfer_nft(&escrow, Some(Pubkey::new_unique()))], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::MetadataMismatch);
+
+    process(&mut context, &[transfer_nft(&escrow, Some(creator))], &[&escrow.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.vault_token).await, 1);
+}
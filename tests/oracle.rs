@@ -0,0 +1,113 @@
+//! Native and token escrows gated on a locally created mock oracle account
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{account::Account, signature::Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    oracle::OracleLayout,
+    state::{Approvals, Comparison, OracleCondition, Schedule},
+};
+
+const THRESHOLD: i64 = 1_000;
+
+fn price_above_threshold(oracle: Pubkey) -> OracleCondition {
+    OracleCondition {
+        oracle,
+        layout: OracleLayout::Test,
+        comparison: Comparison::GreaterOrEqual,
+        threshold: THRESHOLD,
+    }
+}
+
+fn set_oracle_value(context: &mut ProgramTestContext, oracle: &Pubkey, value: i64) {
+    context.set_account(
+        oracle,
+        &Account {
+            lamports: 1_000_000_000,
+            data: value.to_le_bytes().to_vec(),
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        }
+        .into(),
+    );
+}
+
+#[tokio::test]
+async fn native_withdraw_waits_for_oracle() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, 1_000_000_000).await;
+    let oracle = Pubkey::new_unique();
+    set_oracle_value(&mut context, &oracle, THRESHOLD - 1);
+
+    // schedule, hashlock, arbiter, approvals and release authority precede the condition
+    let mut create = escrow.create(0, 0);
+    (
+        Schedule::Cliff,
+        None::<[u8; 32]>,
+        None::<Pubkey>,
+        None::<Approvals>,
+        None::<Pubkey>,
+        Some(price_above_threshold(oracle)),
+    )
+        .serialize(&mut create.data)
+        .unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    let mut withdraw = escrow.withdraw(escrow.amount);
+    withdraw.accounts.push(AccountMeta::new_readonly(oracle, false));
+
+    let result = process(&mut context, &[withdraw.clone()], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::OracleConditionNotMet);
+
+    // another account holding a satisfying value is not the escrow's oracle
+    let impostor = Pubkey::new_unique();
+    set_oracle_value(&mut context, &impostor, THRESHOLD);
+    let mut impostor_withdraw = escrow.withdraw(escrow.amount);
+    impostor_withdraw.accounts.push(AccountMeta::new_readonly(impostor, false));
+    let result = process(&mut context, &[impostor_withdraw], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::InvalidOracleAccount);
+
+    set_oracle_value(&mut context, &oracle, THRESHOLD);
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    process(&mut context, &[withdraw], &[&escrow.receiver]).await.unwrap();
+    let received = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports - balance;
+    assert_eq!(received, escrow.amount);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn token_withdraw_waits_for_oracle() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let oracle = Pubkey::new_unique();
+    set_oracle_value(&mut context, &oracle, THRESHOLD - 1);
+
+    // schedule and approvals precede the condition
+    let mut create = escrow.create(0, 0);
+    (Schedule::Cliff, None::<Approvals>, Some(price_above_threshold(oracle)))
+        .serialize(&mut create.data)
+        .unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    let mut withdraw = escrow.withdraw(DEPOSIT);
+    withdraw.accounts.push(AccountMeta::new_readonly(oracle, false));
+
+    let result = process(&mut context, &[withdraw.clone()], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::OracleConditionNotMet);
+
+    // missing oracle account
+    let result = process(&mut context, &[escrow.withdraw(DEPOSIT)], &[&escrow.receiver]).await;
+    assert!(result.is_err());
+
+    set_oracle_value(&mut context, &oracle, THRESHOLD + 1);
+    process(&mut context, &[withdraw], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}
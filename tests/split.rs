@@ -0,0 +1,113 @@
+//! Split escrows pay each receiver its basis point share, the dust goes back to the sender
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    find_config_address, find_split_escrow_address, find_vault_address,
+    state::{SplitInput, SplitShare},
+};
+
+/// Not divisible by the shares, one lamport of dust is left after every share is paid
+const AMOUNT: u64 = 1_000_001;
+
+struct Split {
+    program_id: Pubkey,
+    sender: Keypair,
+    escrow: Pubkey,
+    vault: Pubkey,
+}
+
+impl Split {
+    fn create(&self, shares: &[(&Keypair, u16)]) -> Instruction {
+        let mut data = vec![14];
+        SplitInput {
+            start_time: 0,
+            amount: AMOUNT,
+            nonce: 0,
+            lock_duration: 0,
+            recipients: shares
+                .iter()
+                .map(|(receiver, share_bps)| SplitShare { receiver: receiver.pubkey(), share_bps: *share_bps })
+                .collect(),
+        }
+        .serialize(&mut data)
+        .unwrap();
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new_readonly(find_config_address(&self.program_id).0, false),
+            ],
+            data,
+        }
+    }
+
+    fn withdraw(&self, receiver: &Keypair) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.sender.pubkey(), false),
+                AccountMeta::new(receiver.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(self.vault, false),
+            ],
+            data: vec![15],
+        }
+    }
+}
+
+#[tokio::test]
+async fn receivers_withdraw_their_shares() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let sender = Keypair::new();
+    fund(&mut context, &sender.pubkey(), 1_000_000_000).await;
+    let receivers = [Keypair::new(), Keypair::new(), Keypair::new()];
+    for receiver in &receivers {
+        fund(&mut context, &receiver.pubkey(), 1_000_000_000).await;
+    }
+    let (escrow, _) = find_split_escrow_address(&program_id, &sender.pubkey(), 0);
+    let (vault, _) = find_vault_address(&program_id, &escrow);
+    let split = Split { program_id, sender, escrow, vault };
+    let sender_balance = get_account(&mut context, &split.sender.pubkey()).await.unwrap().lamports;
+
+    let shares = [(&receivers[0], 3_333), (&receivers[1], 3_333), (&receivers[2], 3_334)];
+    process(&mut context, &[split.create(&shares)], &[&split.sender]).await.unwrap();
+
+    let outsider = Keypair::new();
+    fund(&mut context, &outsider.pubkey(), 1_000_000_000).await;
+    let result = process(&mut context, &[split.withdraw(&outsider)], &[&outsider]).await;
+    assert_escrow_error(result, EscrowError::RecipientNotFound);
+
+    // shares are rounded down: 333_300, 333_300 and 333_400
+    for (index, ((receiver, _), expected)) in shares.iter().zip([333_300, 333_300, 333_400]).enumerate() {
+        let balance = get_account(&mut context, &receiver.pubkey()).await.unwrap().lamports;
+        process(&mut context, &[split.withdraw(receiver)], &[receiver]).await.unwrap();
+        let received = get_account(&mut context, &receiver.pubkey()).await.unwrap().lamports - balance;
+        assert_eq!(received, expected);
+
+        if index == 0 {
+            let result = process(&mut context, &[split.withdraw(receiver)], &[receiver]).await;
+            assert_escrow_error(result, EscrowError::AlreadyClaimed);
+        }
+    }
+
+    // the last claim closes the escrow and the lamport of dust is back with the sender
+    assert!(get_account(&mut context, &split.escrow).await.is_none());
+    assert!(get_account(&mut context, &split.vault).await.is_none());
+    let balance = get_account(&mut context, &split.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(sender_balance - balance, AMOUNT - 1);
+}
@@ -0,0 +1,102 @@
+//! Native and token streams accruing per second until withdrawn or stopped
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::instruction::Instruction;
+use solana_sdk::signature::Signer;
+use transfer_solana_program::{error::EscrowError, state::Schedule};
+
+const AMOUNT: u64 = 1_000_000;
+const RATE: u64 = 10_000;
+
+fn stream() -> Schedule {
+    Schedule::Stream { rate: RATE, stopped_at: 0, checkpoint_time: 0, checkpoint_amount: 0 }
+}
+
+fn with_stream(mut create: Instruction) -> Instruction {
+    stream().serialize(&mut create.data).unwrap();
+    create
+}
+
+/// `StopStream` and `StopStreamToken` take the accounts of `Cancel` and `CancelToken`
+fn stop(mut cancel: Instruction, tag: u8) -> Instruction {
+    cancel.data = vec![tag];
+    cancel
+}
+
+#[tokio::test]
+async fn native_stream_withdraw_and_stop() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, AMOUNT).await;
+    let start = unix_timestamp(&mut context).await;
+    let sender_balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    let receiver_balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[with_stream(escrow.create(start, 0))], &[&escrow.sender]).await.unwrap();
+
+    // the amount asked for is ignored, everything accrued is paid
+    set_unix_timestamp(&mut context, start + 30).await;
+    process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, 30 * RATE);
+
+    set_unix_timestamp(&mut context, start + 50).await;
+    process(&mut context, &[stop(escrow.cancel(), 10)], &[&escrow.sender]).await.unwrap();
+    let result = process(&mut context, &[stop(escrow.cancel(), 10)], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::StreamStopped);
+
+    // nothing accrues after the stop, the last 20 seconds close the escrow
+    set_unix_timestamp(&mut context, start + 80).await;
+    process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.unwrap();
+    let balance = get_account(&mut context, &escrow.receiver.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance - receiver_balance, 50 * RATE);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault).await.is_none());
+
+    let balance = get_account(&mut context, &escrow.sender.pubkey()).await.unwrap().lamports;
+    assert_eq!(sender_balance - balance, 50 * RATE);
+}
+
+#[tokio::test]
+async fn token_stream_stop_refunds_and_sweeps_the_vault() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let now = unix_timestamp(&mut context).await;
+
+    // streaming starts in the future, stopping now refunds everything
+    process(&mut context, &[with_stream(escrow.create(now + 100, 0))], &[&escrow.sender]).await.unwrap();
+    mint_to(&mut context, &escrow.token_program_id, &escrow.mint, &escrow.vault_token, 1).await;
+
+    process(&mut context, &[stop(escrow.cancel(), 11)], &[&escrow.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.sender_token).await, DEPOSIT + 1);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
+
+#[tokio::test]
+async fn token_stream_stop_keeps_the_accrued_part() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let start = unix_timestamp(&mut context).await;
+
+    let mut create = escrow.create(start, 0);
+    Schedule::Stream { rate: 100, stopped_at: 0, checkpoint_time: 0, checkpoint_amount: 0 }
+        .serialize(&mut create.data)
+        .unwrap();
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    set_unix_timestamp(&mut context, start + 40).await;
+    process(&mut context, &[stop(escrow.cancel(), 11)], &[&escrow.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.sender_token).await, DEPOSIT - 4_000);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_some());
+
+    process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, 4_000);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
@@ -0,0 +1,175 @@
+//! Token swaps, the deposit of token A given to whoever pays token B
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{error::EscrowError, find_swap_address, find_vault_address, state::SwapInput, token};
+
+const AMOUNT_B: u64 = 3_000;
+
+/// A swap of `DEPOSIT` SPL Token A for `AMOUNT_B` of token B
+struct Swap {
+    program_id: Pubkey,
+    initializer: Keypair,
+    taker: Keypair,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    token_program_b: Pubkey,
+    initializer_token_a: Pubkey,
+    initializer_token_b: Pubkey,
+    taker_token_a: Pubkey,
+    taker_token_b: Pubkey,
+    swap: Pubkey,
+    vault: Pubkey,
+    vault_token: Pubkey,
+}
+
+impl Swap {
+    async fn new(context: &mut ProgramTestContext, program_id: Pubkey, transfer_fee_b: Option<(u16, u64)>) -> Self {
+        let initializer = Keypair::new();
+        let taker = Keypair::new();
+        fund(context, &initializer.pubkey(), 1_000_000_000).await;
+        fund(context, &taker.pubkey(), 1_000_000_000).await;
+
+        let token_program_b = if transfer_fee_b.is_some() { spl_token_2022::id() } else { spl_token::id() };
+        let mint_a = create_mint(context, &spl_token::id(), 6, None).await;
+        let mint_b = create_mint(context, &token_program_b, 6, transfer_fee_b).await;
+        let initializer_token_a =
+            create_token_account(context, &spl_token::id(), &mint_a, &initializer.pubkey(), DEPOSIT).await;
+        let taker_token_b = create_token_account(context, &token_program_b, &mint_b, &taker.pubkey(), AMOUNT_B).await;
+
+        let (swap, _) = find_swap_address(&program_id, &initializer.pubkey(), 0);
+        let (vault, _) = find_vault_address(&program_id, &swap);
+        Self {
+            program_id,
+            initializer_token_b: token::get_associated_token_address(&initializer.pubkey(), &mint_b, &token_program_b),
+            taker_token_a: token::get_associated_token_address(&taker.pubkey(), &mint_a, &spl_token::id()),
+            vault_token: token::get_associated_token_address(&vault, &mint_a, &spl_token::id()),
+            initializer,
+            taker,
+            mint_a,
+            mint_b,
+            token_program_b,
+            initializer_token_a,
+            taker_token_b,
+            swap,
+            vault,
+        }
+    }
+
+    fn create(&self) -> Instruction {
+        let mut data = vec![24];
+        SwapInput { amount_a: DEPOSIT, amount_b: AMOUNT_B, nonce: 0 }.serialize(&mut data).unwrap();
+        let accounts = vec![
+            AccountMeta::new(self.swap, false),
+            AccountMeta::new(self.initializer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(self.mint_a, false),
+            AccountMeta::new_readonly(self.mint_b, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(self.token_program_b, false),
+            AccountMeta::new(self.initializer_token_a, false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(self.vault, false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    fn take(&self) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(self.swap, false),
+            AccountMeta::new(self.taker.pubkey(), true),
+            AccountMeta::new(self.initializer.pubkey(), false),
+            AccountMeta::new_readonly(self.vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(self.mint_a, false),
+            AccountMeta::new_readonly(self.mint_b, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(self.token_program_b, false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new(self.taker_token_a, false),
+            AccountMeta::new(self.taker_token_b, false),
+            AccountMeta::new(self.initializer_token_b, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data: vec![25] }
+    }
+
+    fn cancel(&self) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(self.swap, false),
+            AccountMeta::new(self.initializer.pubkey(), true),
+            AccountMeta::new_readonly(self.vault, false),
+            AccountMeta::new(self.mint_a, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.vault_token, false),
+            AccountMeta::new(self.initializer_token_a, false),
+        ];
+        Instruction { program_id: self.program_id, accounts, data: vec![26] }
+    }
+}
+
+#[tokio::test]
+async fn take_pays_both_sides_and_sweeps_the_vault() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let swap = Swap::new(&mut context, program_id, None).await;
+    let initializer_balance = get_account(&mut context, &swap.initializer.pubkey()).await.unwrap().lamports;
+
+    process(&mut context, &[swap.create()], &[&swap.initializer]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &swap.vault_token).await, DEPOSIT);
+
+    // tokens sent to the vault on top of the deposit must not keep it from closing
+    mint_to(&mut context, &spl_token::id(), &swap.mint_a, &swap.vault_token, 5).await;
+
+    process(&mut context, &[swap.take()], &[&swap.taker]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &swap.taker_token_a).await, DEPOSIT + 5);
+    assert_eq!(token_balance(&mut context, &swap.taker_token_b).await, 0);
+    assert_eq!(token_balance(&mut context, &swap.initializer_token_a).await, 0);
+    assert_eq!(token_balance(&mut context, &swap.initializer_token_b).await, AMOUNT_B);
+    assert!(get_account(&mut context, &swap.swap).await.is_none());
+    assert!(get_account(&mut context, &swap.vault_token).await.is_none());
+
+    // the taker paid for the initializer's token B account, the rents came back
+    let balance = get_account(&mut context, &swap.initializer.pubkey()).await.unwrap().lamports;
+    assert_eq!(balance, initializer_balance);
+}
+
+#[tokio::test]
+async fn cancel_returns_the_deposit() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let swap = Swap::new(&mut context, program_id, None).await;
+
+    process(&mut context, &[swap.create()], &[&swap.initializer]).await.unwrap();
+    mint_to(&mut context, &spl_token::id(), &swap.mint_a, &swap.vault_token, 5).await;
+
+    process(&mut context, &[swap.cancel()], &[&swap.initializer]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &swap.initializer_token_a).await, DEPOSIT + 5);
+    assert!(get_account(&mut context, &swap.swap).await.is_none());
+    assert!(get_account(&mut context, &swap.vault_token).await.is_none());
+
+    let result = process(&mut context, &[swap.take()], &[&swap.taker]).await;
+    assert_escrow_error(result, EscrowError::InvalidEscrowOwner);
+}
+
+#[tokio::test]
+async fn fee_bearing_mint_b_is_rejected() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let swap = Swap::new(&mut context, program_id, Some((100, u64::MAX))).await;
+
+    let result = process(&mut context, &[swap.create()], &[&swap.initializer]).await;
+    assert_escrow_error(result, EscrowError::TransferFeeNotSupported);
+}
@@ -0,0 +1,148 @@
+//! Token escrows frozen by a dispute and settled by their arbiter
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use transfer_solana_program::{
+    error::EscrowError,
+    state::{Approvals, OracleCondition, ResolveDisputeInput, Schedule, UiAmount, UpdateScheduleInput},
+};
+
+/// Token escrow creation with `arbiter` in the trailing optional fields
+fn create_with_arbiter(escrow: &TokenEscrow, start_time: u64, lock_duration: u64, arbiter: &Pubkey) -> Instruction {
+    let mut create = escrow.create(start_time, lock_duration);
+    (
+        Schedule::Cliff,
+        None::<Approvals>,
+        None::<OracleCondition>,
+        None::<UiAmount>,
+        Some(*arbiter),
+    )
+        .serialize(&mut create.data)
+        .unwrap();
+    create
+}
+
+fn raise_dispute(escrow: &TokenEscrow, party: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new_readonly(*party, true),
+        ],
+        data: vec![31],
+    }
+}
+
+fn resolve_dispute(escrow: &TokenEscrow, arbiter: &Pubkey, release: bool, destination: &Pubkey) -> Instruction {
+    let mut data = vec![32];
+    ResolveDisputeInput { release }.serialize(&mut data).unwrap();
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new_readonly(*arbiter, true),
+            AccountMeta::new(escrow.sender.pubkey(), false),
+            AccountMeta::new_readonly(escrow.vault, false),
+            AccountMeta::new(escrow.mint, false),
+            AccountMeta::new_readonly(escrow.token_program_id, false),
+            AccountMeta::new(escrow.vault_token, false),
+            AccountMeta::new(*destination, false),
+        ],
+        data,
+    }
+}
+
+fn update_schedule(escrow: &TokenEscrow) -> Instruction {
+    let mut data = vec![13];
+    UpdateScheduleInput { end_time: 0, rate: 1 }.serialize(&mut data).unwrap();
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new_readonly(escrow.sender.pubkey(), true),
+        ],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn arbiter_releases_disputed_escrow() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let arbiter = Keypair::new();
+    create_token_account(&mut context, &escrow.token_program_id, &escrow.mint, &escrow.receiver.pubkey(), 0).await;
+
+    let now = unix_timestamp(&mut context).await;
+    let create = create_with_arbiter(&escrow, now, LONG_LOCK, &arbiter.pubkey());
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    process(&mut context, &[raise_dispute(&escrow, &escrow.receiver.pubkey())], &[&escrow.receiver]).await.unwrap();
+    let result = process(&mut context, &[raise_dispute(&escrow, &escrow.sender.pubkey())], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+
+    // the sender cannot take the tokens back or change the terms while the dispute is open
+    let result = process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+    let result = process(&mut context, &[update_schedule(&escrow)], &[&escrow.sender]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+
+    let intruder = Keypair::new();
+    let result = process(
+        &mut context,
+        &[resolve_dispute(&escrow, &intruder.pubkey(), true, &escrow.receiver_token)],
+        &[&intruder],
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::ArbiterMismatch);
+
+    // releasing only pays the receiver's own token account
+    let release_to_sender = resolve_dispute(&escrow, &arbiter.pubkey(), true, &escrow.sender_token);
+    assert!(process(&mut context, &[release_to_sender], &[&arbiter]).await.is_err());
+
+    let release = resolve_dispute(&escrow, &arbiter.pubkey(), true, &escrow.receiver_token);
+    process(&mut context, &[release], &[&arbiter]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
+
+#[tokio::test]
+async fn arbiter_refunds_fee_mint_escrow() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token_2022::id(), Some((100, u64::MAX))).await;
+    let arbiter = Keypair::new();
+
+    let create = create_with_arbiter(&escrow, 0, 0, &arbiter.pubkey());
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    process(&mut context, &[raise_dispute(&escrow, &escrow.sender.pubkey())], &[&escrow.sender]).await.unwrap();
+    let result = process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::EscrowDisputed);
+
+    let refund = resolve_dispute(&escrow, &arbiter.pubkey(), false, &escrow.sender_token);
+    process(&mut context, &[refund], &[&arbiter]).await.unwrap();
+    // 100 withheld by the vault on deposit, 99 on the refund of 9_900
+    assert_eq!(token_balance(&mut context, &escrow.sender_token).await, 9_900 - 99);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
+
+#[tokio::test]
+async fn dispute_needs_an_arbiter() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+
+    process(&mut context, &[escrow.create(0, 0)], &[&escrow.sender]).await.unwrap();
+    let result = process(&mut context, &[raise_dispute(&escrow, &escrow.receiver.pubkey())], &[&escrow.receiver]).await;
+    assert_escrow_error(result, EscrowError::NoArbiter);
+}
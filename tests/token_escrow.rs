@@ -0,0 +1,97 @@
+//! Token escrows funded, withdrawn and cancelled with SPL Token and Token-2022 mints
+
+mod common;
+
+use common::*;
+use solana_program::pubkey::Pubkey;
+
+/// 1% transfer fee, capped well above anything transferred here
+const FEE_BASIS_POINTS: u16 = 100;
+
+/// Funds an unlocked escrow, withdraws it in two parts and checks everything got closed.
+/// Returns what the receiver ended up with.
+async fn create_and_withdraw(token_program_id: Pubkey, transfer_fee: Option<(u16, u64)>) -> u64 {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, token_program_id, transfer_fee).await;
+
+    process(&mut context, &[escrow.create(0, 0)], &[&escrow.sender]).await.unwrap();
+    let received = token_balance(&mut context, &escrow.vault_token).await;
+    assert_eq!(token_balance(&mut context, &escrow.sender_token).await, 0);
+
+    process(&mut context, &[escrow.withdraw(received / 2)], &[&escrow.receiver]).await.unwrap();
+    assert!(get_account(&mut context, &escrow.escrow).await.is_some());
+
+    // asking for more than is left fails
+    assert!(process(&mut context, &[escrow.withdraw(received)], &[&escrow.receiver]).await.is_err());
+
+    process(&mut context, &[escrow.withdraw(received - received / 2)], &[&escrow.receiver]).await.unwrap();
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+
+    assert_eq!(received, DEPOSIT - transfer_fee.map_or(0, |(basis_points, _)| DEPOSIT * basis_points as u64 / 10_000));
+    token_balance(&mut context, &escrow.receiver_token).await
+}
+
+/// Funds a locked escrow and cancels it, returns what the sender got back
+async fn create_and_cancel(token_program_id: Pubkey, transfer_fee: Option<(u16, u64)>) -> u64 {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, token_program_id, transfer_fee).await;
+
+    let now = unix_timestamp(&mut context).await;
+    process(&mut context, &[escrow.create(now, LONG_LOCK)], &[&escrow.sender]).await.unwrap();
+
+    // the receiver cannot withdraw before the lock expires
+    assert!(process(&mut context, &[escrow.withdraw(1)], &[&escrow.receiver]).await.is_err());
+
+    process(&mut context, &[escrow.cancel()], &[&escrow.sender]).await.unwrap();
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+    token_balance(&mut context, &escrow.sender_token).await
+}
+
+#[tokio::test]
+async fn spl_token_create_and_withdraw() {
+    assert_eq!(create_and_withdraw(spl_token::id(), None).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn spl_token_create_and_cancel() {
+    assert_eq!(create_and_cancel(spl_token::id(), None).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn token_2022_create_and_withdraw() {
+    assert_eq!(create_and_withdraw(spl_token_2022::id(), None).await, DEPOSIT);
+}
+
+#[tokio::test]
+async fn token_2022_transfer_fee_create_and_withdraw() {
+    // 100 withheld by the vault on deposit, then 1% of each withdrawal of 4_950
+    let received = create_and_withdraw(spl_token_2022::id(), Some((FEE_BASIS_POINTS, u64::MAX))).await;
+    assert_eq!(received, 9_900 - 2 * 50);
+}
+
+#[tokio::test]
+async fn token_2022_transfer_fee_create_and_cancel() {
+    // 100 withheld by the vault on deposit, 99 on the refund of 9_900
+    let refunded = create_and_cancel(spl_token_2022::id(), Some((FEE_BASIS_POINTS, u64::MAX))).await;
+    assert_eq!(refunded, 9_900 - 99);
+}
+
+#[tokio::test]
+async fn withdraw_sweeps_tokens_sent_to_the_vault() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+
+    process(&mut context, &[escrow.create(0, 0)], &[&escrow.sender]).await.unwrap();
+    mint_to(&mut context, &escrow.token_program_id, &escrow.mint, &escrow.vault_token, 1).await;
+
+    // the last withdrawal pays out the whole vault so it can close
+    process(&mut context, &[escrow.withdraw(DEPOSIT)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT + 1);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+    assert!(get_account(&mut context, &escrow.vault_token).await.is_none());
+}
@@ -0,0 +1,161 @@
+//! Top ups and rate changes checkpoint what streams accrued so far
+
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use common::*;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::signature::Signer;
+use transfer_solana_program::state::{Escrow, Schedule, TransferToken, UiAmount, UpdateScheduleInput};
+
+fn stream(rate: u64) -> Schedule {
+    Schedule::Stream { rate, stopped_at: 0, checkpoint_time: 0, checkpoint_amount: 0 }
+}
+
+fn with_schedule(mut create: Instruction, schedule: Schedule) -> Instruction {
+    schedule.serialize(&mut create.data).unwrap();
+    create
+}
+
+fn top_up(escrow: &NativeEscrow, amount: u64) -> Instruction {
+    let mut data = vec![8];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new(escrow.sender.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(escrow.vault, false),
+        ],
+        data,
+    }
+}
+
+fn top_up_token(escrow: &TokenEscrow, ui_amount: UiAmount) -> Instruction {
+    let mut data = vec![9];
+    data.extend_from_slice(&0u64.to_le_bytes());
+    Some(ui_amount).serialize(&mut data).unwrap();
+    Instruction {
+        program_id: escrow.program_id,
+        accounts: vec![
+            AccountMeta::new(escrow.escrow, false),
+            AccountMeta::new_readonly(escrow.sender.pubkey(), true),
+            AccountMeta::new_readonly(escrow.vault, false),
+            AccountMeta::new_readonly(escrow.mint, false),
+            AccountMeta::new_readonly(escrow.token_program_id, false),
+            AccountMeta::new(escrow.vault_token, false),
+            AccountMeta::new(escrow.sender_token, false),
+        ],
+        data,
+    }
+}
+
+/// `UpdateSchedule` for native escrows, `UpdateScheduleToken` for token escrows
+fn update_schedule(program_id: Pubkey, tag: u8, escrow: &Pubkey, sender: &Pubkey, rate: u64) -> Instruction {
+    let mut data = vec![tag];
+    UpdateScheduleInput { end_time: 0, rate }.serialize(&mut data).unwrap();
+    Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new(*escrow, false), AccountMeta::new_readonly(*sender, true)],
+        data,
+    }
+}
+
+async fn lamports(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    get_account(context, address).await.unwrap().lamports
+}
+
+#[tokio::test]
+async fn native_top_up_after_the_budget_ran_out() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, 1_000_000).await;
+    let start = unix_timestamp(&mut context).await;
+    let receiver_balance = lamports(&mut context, &escrow.receiver.pubkey()).await;
+
+    let create = with_schedule(escrow.create(start, 0), stream(10_000));
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    // the budget of 100 seconds ran out long ago, the top up streams from now on
+    set_unix_timestamp(&mut context, start + 200).await;
+    process(&mut context, &[top_up(&escrow, 500_000)], &[&escrow.sender]).await.unwrap();
+    let account = get_account(&mut context, &escrow.escrow).await.unwrap();
+    let escrow_data = Escrow::try_from_slice(&account.data).unwrap();
+    assert_eq!(escrow_data.amount_to_send, 1_500_000);
+    assert_eq!(
+        escrow_data.schedule,
+        Schedule::Stream { rate: 10_000, stopped_at: 0, checkpoint_time: start + 200, checkpoint_amount: 1_000_000 }
+    );
+
+    process(&mut context, &[escrow.withdraw(0)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(lamports(&mut context, &escrow.receiver.pubkey()).await - receiver_balance, 1_000_000);
+
+    set_unix_timestamp(&mut context, start + 230).await;
+    process(&mut context, &[escrow.withdraw(0)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(lamports(&mut context, &escrow.receiver.pubkey()).await - receiver_balance, 1_300_000);
+}
+
+#[tokio::test]
+async fn native_rate_change_keeps_the_accrued_amount() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = NativeEscrow::new(&mut context, program_id, 1_000_000).await;
+    let start = unix_timestamp(&mut context).await;
+    let receiver_balance = lamports(&mut context, &escrow.receiver.pubkey()).await;
+
+    let create = with_schedule(escrow.create(start, 0), stream(10_000));
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    set_unix_timestamp(&mut context, start + 10).await;
+    let update = update_schedule(program_id, 12, &escrow.escrow, &escrow.sender.pubkey(), 20_000);
+    process(&mut context, &[update], &[&escrow.sender]).await.unwrap();
+
+    // 10 seconds at the old rate, 10 at the new one
+    set_unix_timestamp(&mut context, start + 20).await;
+    process(&mut context, &[escrow.withdraw(0)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(lamports(&mut context, &escrow.receiver.pubkey()).await - receiver_balance, 300_000);
+}
+
+#[tokio::test]
+async fn token_top_up_and_rate_change() {
+    let (program_test, program_id) = program_test();
+    let mut context = program_test.start_with_context().await;
+    let escrow = TokenEscrow::new(&mut context, program_id, spl_token::id(), None).await;
+    let start = unix_timestamp(&mut context).await;
+    mint_to(&mut context, &escrow.token_program_id, &escrow.mint, &escrow.sender_token, 5_000).await;
+
+    let create = with_schedule(escrow.create(start, 0), stream(100));
+    process(&mut context, &[create], &[&escrow.sender]).await.unwrap();
+
+    // the deposit streamed out after 100 seconds, 0.005 tokens of 6 decimals are 5_000
+    set_unix_timestamp(&mut context, start + 200).await;
+    let ui_amount = UiAmount { amount: "0.005".to_string(), decimals: 6 };
+    process(&mut context, &[top_up_token(&escrow, ui_amount)], &[&escrow.sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.vault_token).await, DEPOSIT + 5_000);
+
+    set_unix_timestamp(&mut context, start + 220).await;
+    let update = update_schedule(program_id, 13, &escrow.escrow, &escrow.sender.pubkey(), 50);
+    process(&mut context, &[update], &[&escrow.sender]).await.unwrap();
+    let account = get_account(&mut context, &escrow.escrow).await.unwrap();
+    let escrow_data = TransferToken::try_from_slice(&account.data).unwrap();
+    assert_eq!(escrow_data.amount, DEPOSIT + 5_000);
+    assert_eq!(
+        escrow_data.schedule,
+        Schedule::Stream { rate: 50, stopped_at: 0, checkpoint_time: start + 220, checkpoint_amount: DEPOSIT + 2_000 }
+    );
+
+    set_unix_timestamp(&mut context, start + 240).await;
+    process(&mut context, &[escrow.withdraw(0)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT + 3_000);
+
+    set_unix_timestamp(&mut context, start + 1_000).await;
+    process(&mut context, &[escrow.withdraw(0)], &[&escrow.receiver]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &escrow.receiver_token).await, DEPOSIT + 5_000);
+    assert!(get_account(&mut context, &escrow.escrow).await.is_none());
+}